@@ -0,0 +1,15 @@
+-- DATETIME carries no timezone; the application always writes UTC.
+
+CREATE TABLE IF NOT EXISTS power_records (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    remaining_energy DOUBLE NOT NULL,
+    remaining_money DOUBLE NOT NULL,
+    meter_room_id VARCHAR(255) NOT NULL,
+    room_display_name VARCHAR(255) NOT NULL,
+    room_id VARCHAR(255) NOT NULL,
+    building_id VARCHAR(255) NOT NULL,
+    campus_id VARCHAR(255) NOT NULL,
+    room_number VARCHAR(255) NOT NULL,
+    created_at DATETIME(6) NOT NULL,
+    INDEX idx_power_records_room_created_at (room_id, created_at)
+);
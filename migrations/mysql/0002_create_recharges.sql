@@ -0,0 +1,12 @@
+-- DATETIME carries no timezone; the application always writes UTC.
+
+CREATE TABLE IF NOT EXISTS recharges (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    room_id VARCHAR(255) NOT NULL,
+    room_display_name VARCHAR(255) NOT NULL,
+    amount DOUBLE NOT NULL,
+    previous_money DOUBLE NOT NULL,
+    current_money DOUBLE NOT NULL,
+    created_at DATETIME(6) NOT NULL,
+    INDEX idx_recharges_room_created_at (room_id, created_at)
+);
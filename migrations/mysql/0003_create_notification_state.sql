@@ -0,0 +1,12 @@
+-- DATETIME carries no timezone; the application always writes UTC.
+
+CREATE TABLE IF NOT EXISTS notification_state (
+    account VARCHAR(255) NOT NULL PRIMARY KEY,
+    low_balance_threshold DOUBLE NULL,
+    last_low_balance_notify_at DATETIME(6) NULL,
+    last_heartbeat_date DATE NULL,
+    consecutive_fetch_failures BIGINT NOT NULL DEFAULT 0,
+    last_fetch_failure_notify_at DATETIME(6) NULL,
+    last_depletion_notify_at DATETIME(6) NULL,
+    updated_at DATETIME(6) NOT NULL
+);
@@ -0,0 +1,14 @@
+-- DATETIME carries no timezone; the application always writes UTC.
+
+CREATE TABLE IF NOT EXISTS notifications (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    account VARCHAR(255) NOT NULL,
+    event VARCHAR(64) NOT NULL,
+    channel VARCHAR(255) NOT NULL,
+    channel_type VARCHAR(64) NOT NULL,
+    success BOOLEAN NOT NULL,
+    error_class VARCHAR(64) NULL,
+    retries BIGINT NOT NULL DEFAULT 0,
+    created_at DATETIME(6) NOT NULL,
+    INDEX idx_notifications_account_created_at (account, created_at)
+);
@@ -0,0 +1,15 @@
+-- DATETIME carries no timezone; the application always writes UTC.
+
+CREATE TABLE IF NOT EXISTS notification_outbox (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    account VARCHAR(255) NOT NULL,
+    channel VARCHAR(255) NOT NULL,
+    event VARCHAR(64) NOT NULL,
+    payload TEXT NOT NULL,
+    attempts BIGINT NOT NULL DEFAULT 0,
+    error_class VARCHAR(64) NULL,
+    next_attempt_at DATETIME(6) NOT NULL,
+    expires_at DATETIME(6) NOT NULL,
+    created_at DATETIME(6) NOT NULL,
+    INDEX idx_notification_outbox_account_next_attempt_at (account, next_attempt_at)
+);
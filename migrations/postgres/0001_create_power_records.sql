@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS power_records (
+    id BIGSERIAL PRIMARY KEY,
+    remaining_energy DOUBLE PRECISION NOT NULL,
+    remaining_money DOUBLE PRECISION NOT NULL,
+    meter_room_id TEXT NOT NULL,
+    room_display_name TEXT NOT NULL,
+    room_id TEXT NOT NULL,
+    building_id TEXT NOT NULL,
+    campus_id TEXT NOT NULL,
+    room_number TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_power_records_room_created_at
+    ON power_records (room_id, created_at);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS recharges (
+    id BIGSERIAL PRIMARY KEY,
+    room_id TEXT NOT NULL,
+    room_display_name TEXT NOT NULL,
+    amount DOUBLE PRECISION NOT NULL,
+    previous_money DOUBLE PRECISION NOT NULL,
+    current_money DOUBLE PRECISION NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_recharges_room_created_at
+    ON recharges (room_id, created_at);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS notification_state (
+    account TEXT PRIMARY KEY,
+    low_balance_threshold DOUBLE PRECISION,
+    last_low_balance_notify_at TIMESTAMPTZ,
+    last_heartbeat_date DATE,
+    consecutive_fetch_failures BIGINT NOT NULL DEFAULT 0,
+    last_fetch_failure_notify_at TIMESTAMPTZ,
+    last_depletion_notify_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS notifications (
+    id BIGSERIAL PRIMARY KEY,
+    account TEXT NOT NULL,
+    event TEXT NOT NULL,
+    channel TEXT NOT NULL,
+    channel_type TEXT NOT NULL,
+    success BOOLEAN NOT NULL,
+    error_class TEXT,
+    retries BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_notifications_account_created_at
+    ON notifications (account, created_at);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS power_records (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    remaining_energy REAL NOT NULL,
+    remaining_money REAL NOT NULL,
+    meter_room_id TEXT NOT NULL,
+    room_display_name TEXT NOT NULL,
+    room_id TEXT NOT NULL,
+    building_id TEXT NOT NULL,
+    campus_id TEXT NOT NULL,
+    room_number TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
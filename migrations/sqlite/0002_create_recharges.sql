@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS recharges (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    room_id TEXT NOT NULL,
+    room_display_name TEXT NOT NULL,
+    amount REAL NOT NULL,
+    previous_money REAL NOT NULL,
+    current_money REAL NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
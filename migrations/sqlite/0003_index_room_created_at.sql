@@ -0,0 +1,5 @@
+CREATE INDEX IF NOT EXISTS idx_power_records_room_created_at
+    ON power_records (room_id, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_recharges_room_created_at
+    ON recharges (room_id, created_at);
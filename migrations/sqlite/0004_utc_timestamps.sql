@@ -0,0 +1,56 @@
+-- SQLite has no native timezone-aware type, so timestamps are stored as
+-- RFC 3339 text with an explicit UTC offset ("2024-01-01T08:00:00+00:00").
+-- Existing rows were written by CURRENT_TIMESTAMP, which is already UTC.
+
+CREATE TABLE power_records_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    remaining_energy REAL NOT NULL,
+    remaining_money REAL NOT NULL,
+    meter_room_id TEXT NOT NULL,
+    room_display_name TEXT NOT NULL,
+    room_id TEXT NOT NULL,
+    building_id TEXT NOT NULL,
+    campus_id TEXT NOT NULL,
+    room_number TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%S+00:00', 'now'))
+);
+
+INSERT INTO power_records_new (
+    id, remaining_energy, remaining_money, meter_room_id, room_display_name,
+    room_id, building_id, campus_id, room_number, created_at
+)
+SELECT
+    id, remaining_energy, remaining_money, meter_room_id, room_display_name,
+    room_id, building_id, campus_id, room_number,
+    strftime('%Y-%m-%dT%H:%M:%S+00:00', created_at)
+FROM power_records;
+
+DROP TABLE power_records;
+ALTER TABLE power_records_new RENAME TO power_records;
+
+CREATE INDEX idx_power_records_room_created_at
+    ON power_records (room_id, created_at);
+
+CREATE TABLE recharges_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    room_id TEXT NOT NULL,
+    room_display_name TEXT NOT NULL,
+    amount REAL NOT NULL,
+    previous_money REAL NOT NULL,
+    current_money REAL NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%S+00:00', 'now'))
+);
+
+INSERT INTO recharges_new (
+    id, room_id, room_display_name, amount, previous_money, current_money, created_at
+)
+SELECT
+    id, room_id, room_display_name, amount, previous_money, current_money,
+    strftime('%Y-%m-%dT%H:%M:%S+00:00', created_at)
+FROM recharges;
+
+DROP TABLE recharges;
+ALTER TABLE recharges_new RENAME TO recharges;
+
+CREATE INDEX idx_recharges_room_created_at
+    ON recharges (room_id, created_at);
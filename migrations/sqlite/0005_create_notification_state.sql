@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS notification_state (
+    account TEXT PRIMARY KEY,
+    low_balance_threshold REAL,
+    last_low_balance_notify_at TEXT,
+    last_heartbeat_date TEXT,
+    consecutive_fetch_failures INTEGER NOT NULL DEFAULT 0,
+    last_fetch_failure_notify_at TEXT,
+    last_depletion_notify_at TEXT,
+    updated_at TEXT NOT NULL
+);
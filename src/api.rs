@@ -1,4 +1,5 @@
-use crate::config::{AppConfig, LoginType};
+use crate::config::{AccountConfig, LoginType};
+use crate::metrics::metrics;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, warn};
 use uestc_client::UestcClient;
@@ -7,13 +8,12 @@ const BASE_URL: &str = "https://online.uestc.edu.cn/site";
 
 pub struct ApiService {
     client: UestcClient,
-    config: AppConfig,
+    config: AccountConfig,
 }
 
 impl ApiService {
-    pub async fn new(config: &AppConfig) -> Result<Self, Box<dyn std::error::Error>> {
-        let user_display = config.username.as_deref().unwrap_or("unknown");
-        debug!("Creating new API service for user: {}", user_display);
+    pub async fn new(config: &AccountConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        debug!("Creating new API service for account: {}", config.name);
         let client = UestcClient::with_cookie_file(&config.cookie_file);
 
         let service = Self {
@@ -26,6 +26,12 @@ impl ApiService {
     }
 
     async fn login(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let result = self.try_login().await;
+        metrics().record_login_attempt(&self.config.name, result.is_ok());
+        result
+    }
+
+    async fn try_login(&self) -> Result<(), Box<dyn std::error::Error>> {
         debug!("Attempting login via {:?}", self.config.login_type);
         match self.config.login_type {
             LoginType::Password => {
@@ -166,7 +172,7 @@ impl ApiService {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PowerInfo {
     /// retcode: 返回代码
     #[serde(rename = "retcode")]
@@ -211,12 +217,23 @@ pub struct PowerInfo {
     pub room_number: String,
 }
 
+/// Also accepts plain numbers, which is how the values are serialized again
+/// (e.g. in the notification outbox).
 fn deserialize_f64_from_str<'de, D>(deserializer: D) -> Result<f64, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
-    let s: String = serde::Deserialize::deserialize(deserializer)?;
-    s.parse::<f64>().map_err(serde::de::Error::custom)
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(f64),
+    }
+
+    match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(s) => s.parse::<f64>().map_err(serde::de::Error::custom),
+        StringOrNumber::Number(n) => Ok(n),
+    }
 }
 
 #[derive(Debug, Deserialize)]
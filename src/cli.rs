@@ -0,0 +1,495 @@
+use crate::api::{ApiService, PowerInfo};
+use crate::config::{AccountConfig, AppConfig, LoginType, ThresholdTier};
+use crate::db::{self, DbService, PowerRecord};
+use crate::forecast::ConsumptionForecast;
+use crate::notify::{
+    LowBalanceAlert, NotificationEvent, NotificationManager, PowerReport, create_single_notifier,
+};
+use crate::template;
+use crate::utils::{parse_end_time, parse_time};
+use chrono::{DateTime, Local, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Write};
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use tracing::info;
+
+const DEFAULT_HISTORY_ROWS: i64 = 50;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "UESTC dormitory power balance monitor")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the monitor (default when no command is given)
+    Run,
+    /// Fetch the current balance once and print it, without saving
+    FetchOnce {
+        /// Account name (defaults to the first configured account)
+        #[arg(long)]
+        account: Option<String>,
+        /// Print the raw sample as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print recorded samples as a table
+    History {
+        #[command(flatten)]
+        filter: RecordFilter,
+        /// Number of most recent rows to show
+        #[arg(long, default_value_t = DEFAULT_HISTORY_ROWS, value_parser = clap::value_parser!(i64).range(1..))]
+        limit: i64,
+    },
+    /// Export recorded samples as CSV or JSON
+    Export {
+        #[command(flatten)]
+        filter: RecordFilter,
+        /// Export only the most recent rows
+        #[arg(long, value_parser = clap::value_parser!(i64).range(1..))]
+        limit: Option<i64>,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Send a synthetic event through every configured notifier
+    TestNotify {
+        /// low_balance, heartbeat, login_failure, consecutive_fetch_failures,
+        /// depletion_forecast or recharge
+        #[arg(long, default_value = "low_balance")]
+        event: NotificationEvent,
+        /// Account whose notification settings are used (defaults to the first)
+        #[arg(long)]
+        account: Option<String>,
+        /// Only send to the channels the event is routed to
+        #[arg(long)]
+        routed: bool,
+    },
+    /// Print the notification delivery log
+    Notifications {
+        /// Only notifications of this account
+        #[arg(long)]
+        account: Option<String>,
+        /// Only this event (low_balance, heartbeat, login_failure, ...)
+        #[arg(long)]
+        event: Option<NotificationEvent>,
+        /// Start time, RFC 3339 or YYYY-MM-DD[ HH:MM:SS] (local time)
+        #[arg(long, value_parser = parse_time_arg)]
+        from: Option<DateTime<Utc>>,
+        /// End time, RFC 3339 or YYYY-MM-DD[ HH:MM:SS] (local time), a date
+        /// alone includes that day
+        #[arg(long, value_parser = parse_end_time_arg)]
+        to: Option<DateTime<Utc>>,
+        /// Number of most recent rows to show
+        #[arg(long, default_value_t = DEFAULT_HISTORY_ROWS, value_parser = clap::value_parser!(i64).range(1..))]
+        limit: i64,
+    },
+    /// Validate the configuration and exit
+    CheckConfig,
+}
+
+#[derive(Debug, Args)]
+pub struct RecordFilter {
+    /// Only records of this room_id
+    #[arg(long)]
+    room: Option<String>,
+    /// Start time, RFC 3339 or YYYY-MM-DD[ HH:MM:SS] (local time)
+    #[arg(long, value_parser = parse_time_arg)]
+    from: Option<DateTime<Utc>>,
+    /// End time, RFC 3339 or YYYY-MM-DD[ HH:MM:SS] (local time), a date
+    /// alone includes that day
+    #[arg(long, value_parser = parse_end_time_arg)]
+    to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl Command {
+    /// Whether the command prints its result to stdout, in which case logs
+    /// should go to stderr.
+    pub fn writes_stdout(&self) -> bool {
+        !matches!(self, Command::Run)
+    }
+}
+
+fn parse_time_arg(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_time(value.trim()).ok_or_else(|| "expected RFC 3339 or YYYY-MM-DD[ HH:MM:SS]".to_string())
+}
+
+fn parse_end_time_arg(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_end_time(value.trim())
+        .ok_or_else(|| "expected RFC 3339 or YYYY-MM-DD[ HH:MM:SS]".to_string())
+}
+
+pub async fn execute(command: Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Run => crate::run().await,
+        Command::FetchOnce { account, json } => fetch_once(account.as_deref(), json).await,
+        Command::History { filter, limit } => history(&filter, limit).await,
+        Command::Export {
+            filter,
+            limit,
+            format,
+            output,
+        } => export(&filter, limit, format, output).await,
+        Command::TestNotify {
+            event,
+            account,
+            routed,
+        } => test_notify(event, account.as_deref(), routed).await,
+        Command::Notifications {
+            account,
+            event,
+            from,
+            to,
+            limit,
+        } => notifications(account.as_deref(), event, from, to, limit).await,
+        Command::CheckConfig => check_config(),
+    }
+}
+
+fn select_account(config: &AppConfig, name: Option<&str>) -> Result<AccountConfig, Box<dyn Error>> {
+    let accounts = config.get_accounts();
+    match name {
+        Some(name) => accounts
+            .into_iter()
+            .find(|account| account.name == name)
+            .ok_or_else(|| format!("Account '{}' is not configured", name).into()),
+        None => accounts
+            .into_iter()
+            .next()
+            .ok_or_else(|| "No accounts configured".into()),
+    }
+}
+
+async fn open_db(config: &AppConfig) -> Result<DbService, Box<dyn Error>> {
+    let db_service = DbService::new(config.database_url.clone()).await?;
+    db_service.init().await?;
+    Ok(db_service)
+}
+
+async fn fetch_once(account: Option<&str>, json: bool) -> Result<(), Box<dyn Error>> {
+    let config = AppConfig::new()?;
+    let account = select_account(&config, account)?;
+    let api_service = ApiService::new(&account).await?;
+    let info = api_service
+        .fetch_data()
+        .await?
+        .ok_or("No data returned from API")?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else {
+        println!("Account: {}", account.name);
+        println!("Room:    {} ({})", info.room_display_name, info.room_id);
+        println!("Balance: {:.2} CNY", info.remaining_money);
+        println!("Energy:  {:.2} kWh", info.remaining_energy);
+    }
+    Ok(())
+}
+
+async fn history(filter: &RecordFilter, limit: i64) -> Result<(), Box<dyn Error>> {
+    let config = AppConfig::new()?;
+    let db_service = open_db(&config).await?;
+    let records = db_service
+        .history(filter.room.as_deref(), filter.from, filter.to, limit)
+        .await?;
+
+    println!(
+        "{:<19}  {:>13}  {:>12}  ROOM",
+        "TIME", "BALANCE (CNY)", "ENERGY (kWh)"
+    );
+    for record in &records {
+        println!(
+            "{:<19}  {:>13.2}  {:>12.2}  {} ({})",
+            record
+                .created_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S"),
+            record.remaining_money,
+            record.remaining_energy,
+            record.room_display_name,
+            record.room_id
+        );
+    }
+    println!("{} record(s)", records.len());
+    Ok(())
+}
+
+async fn notifications(
+    account: Option<&str>,
+    event: Option<NotificationEvent>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    limit: i64,
+) -> Result<(), Box<dyn Error>> {
+    let config = AppConfig::new()?;
+    let db_service = open_db(&config).await?;
+    let records = db_service
+        .notifications(account, event.map(|e| e.as_str()), from, to, limit)
+        .await?;
+
+    println!(
+        "{:<19}  {:<12}  {:<26}  {:<16}  {:<7}  RESULT",
+        "TIME", "ACCOUNT", "EVENT", "CHANNEL", "RETRIES"
+    );
+    for record in &records {
+        let result = match &record.error_class {
+            None => "sent".to_string(),
+            Some(class) => format!("failed ({})", class),
+        };
+        println!(
+            "{:<19}  {:<12}  {:<26}  {:<16}  {:<7}  {}",
+            record
+                .created_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S"),
+            record.account,
+            record.event,
+            record.channel,
+            record.retries,
+            result
+        );
+    }
+    println!("{} notification(s)", records.len());
+    Ok(())
+}
+
+async fn export(
+    filter: &RecordFilter,
+    limit: Option<i64>,
+    format: ExportFormat,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let config = AppConfig::new()?;
+    let db_service = open_db(&config).await?;
+    let records = db_service
+        .history(
+            filter.room.as_deref(),
+            filter.from,
+            filter.to,
+            limit.unwrap_or(i64::MAX),
+        )
+        .await?;
+
+    let writer: Box<dyn Write> = match &output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    match format {
+        ExportFormat::Csv => write_csv(writer, &records)?,
+        ExportFormat::Json => write_json(writer, &records)?,
+    }
+
+    if let Some(path) = output {
+        info!("Exported {} record(s) to {}", records.len(), path.display());
+    }
+    Ok(())
+}
+
+fn write_csv(writer: Box<dyn Write>, records: &[PowerRecord]) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_json(mut writer: Box<dyn Write>, records: &[PowerRecord]) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer_pretty(&mut writer, records)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+async fn test_notify(
+    event: NotificationEvent,
+    account: Option<&str>,
+    routed: bool,
+) -> Result<(), Box<dyn Error>> {
+    let config = AppConfig::new()?;
+    let account = select_account(&config, account)?;
+    let notify_config = account.notify_config(&config.notify)?;
+    let tiers = notify_config.low_balance_tiers();
+    let manager = NotificationManager::new(notify_config, None)
+        .await
+        .ok_or("Notifications are disabled or no valid notifier is configured")?;
+
+    // the lowest tier, as it is the one that escalates the most
+    let report = sample_report(event, tiers.last().expect("at least one tier"));
+    if manager.send_test(&report, event, routed).await {
+        println!("Test {} notification sent", event.as_str());
+        Ok(())
+    } else {
+        Err("Some notifiers failed, see the log above".into())
+    }
+}
+
+/// A made-up sample that looks plausible for the given event.
+fn sample_report(event: NotificationEvent, tier: &ThresholdTier) -> PowerReport {
+    let remaining_money = match event {
+        NotificationEvent::LowBalance => (tier.threshold / 2.0).max(0.0),
+        _ => tier.threshold + 20.0,
+    };
+    let cny_per_day = 2.5;
+    let days_until_empty = remaining_money / cny_per_day;
+    PowerReport {
+        info: PowerInfo {
+            code: 0,
+            message: "test".to_string(),
+            remaining_energy: remaining_money / 0.6,
+            remaining_money,
+            meter_room_id: "test".to_string(),
+            room_display_name: "Test Room".to_string(),
+            room_id: "test".to_string(),
+            building_id: "test".to_string(),
+            campus_id: "test".to_string(),
+            room_number: "000".to_string(),
+        },
+        forecast: Some(ConsumptionForecast {
+            kwh_per_day: cny_per_day / 0.6,
+            cny_per_day,
+            days_until_empty: Some(days_until_empty),
+            depletion_time: Some(
+                Local::now() + chrono::Duration::seconds((days_until_empty * 86400.0) as i64),
+            ),
+            window_days: 7.0,
+        }),
+        recharge_amount: (event == NotificationEvent::Recharge).then_some(20.0),
+        alert: (event == NotificationEvent::LowBalance).then(|| LowBalanceAlert::from(tier)),
+    }
+}
+
+fn check_config() -> Result<(), Box<dyn Error>> {
+    let config = AppConfig::new()?;
+    let mut problems = Vec::new();
+
+    if !db::is_supported_url(&config.database_url) {
+        problems.push(
+            "database_url: unsupported scheme, expected sqlite://, postgres:// or mysql://"
+                .to_string(),
+        );
+    }
+    if config.interval_seconds == 0 {
+        problems.push("interval_seconds must be greater than 0".to_string());
+    }
+
+    let accounts = config.get_accounts();
+    for account in &accounts {
+        println!(
+            "Account '{}' ({:?} login)",
+            account.name, account.login_type
+        );
+        if account.login_type == LoginType::Password
+            && (account.username.is_none() || account.password.is_none())
+        {
+            problems.push(format!(
+                "[{}] username and password are required for password login",
+                account.name
+            ));
+        }
+
+        let notify_config = match account.notify_config(&config.notify) {
+            Ok(notify_config) => notify_config,
+            Err(e) => {
+                problems.push(e);
+                continue;
+            }
+        };
+        if account.threshold.is_some() && !notify_config.threshold_tiers.is_empty() {
+            problems.push(format!(
+                "[{}] threshold has no effect when notify.threshold_tiers is set",
+                account.name
+            ));
+        }
+        if !notify_config.enabled {
+            println!("  notifications disabled");
+            continue;
+        }
+        let channels = match notify_config.channels() {
+            Ok(channels) => channels,
+            Err(e) => {
+                problems.push(format!("[{}] {}", account.name, e));
+                continue;
+            }
+        };
+        let is_enabled = |name: &String| channels.iter().any(|c| &c.name == name);
+        for tier in &notify_config.threshold_tiers {
+            for channel in tier.channels.iter().filter(|c| !is_enabled(c)) {
+                problems.push(format!(
+                    "[{}] threshold tier {:.2}: channel '{}' is not enabled",
+                    account.name, tier.threshold, channel
+                ));
+            }
+        }
+        for (event, names) in &notify_config.routes {
+            if let Err(e) = event.parse::<NotificationEvent>() {
+                problems.push(format!("[{}] notify.routes.{}: {}", account.name, event, e));
+                continue;
+            }
+            for channel in names.iter().filter(|c| !is_enabled(c)) {
+                problems.push(format!(
+                    "[{}] notify.routes.{}: channel '{}' is not enabled",
+                    account.name, event, channel
+                ));
+            }
+        }
+
+        for channel in &channels {
+            let valid =
+                create_single_notifier(&channel.config, channel.notify_type.clone()).is_some();
+            println!(
+                "  notifier {} ({}): {}",
+                channel.name,
+                channel.notify_type.as_str(),
+                if valid { "ok" } else { "invalid" }
+            );
+            if !valid {
+                problems.push(format!(
+                    "[{}] notifier {} is not configured correctly",
+                    account.name, channel.name
+                ));
+            }
+            // channels may bring their own templates, report each problem once
+            for problem in template::validate(&channel.config) {
+                let problem = format!("[{}] {}", account.name, problem);
+                if !problems.contains(&problem) {
+                    problems.push(problem);
+                }
+            }
+        }
+    }
+
+    if config.server.enabled && config.server.bind.to_socket_addrs().is_err() {
+        problems.push(format!(
+            "server.bind: invalid address '{}'",
+            config.server.bind
+        ));
+    }
+    if config.influx.enabled && reqwest::Url::parse(&config.influx.url).is_err() {
+        problems.push("influx.url: invalid URL".to_string());
+    }
+    if config.mqtt.enabled && config.mqtt.host.is_empty() {
+        problems.push("mqtt.host is required when MQTT is enabled".to_string());
+    }
+
+    if problems.is_empty() {
+        println!("Configuration OK");
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("error: {}", problem);
+    }
+    Err(format!("{} configuration problem(s) found", problems.len()).into())
+}
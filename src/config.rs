@@ -1,6 +1,7 @@
+use chrono::{DateTime, Local, NaiveTime};
 use config::{Config, ConfigError, Environment, File, FileFormat};
-use serde::Deserialize;
-use std::{fs, path::Path};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::Path};
 
 #[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
 #[serde(rename_all = "lowercase")]
@@ -28,6 +29,192 @@ pub struct AppConfig {
     pub interval_seconds: u64,
     #[serde(default)]
     pub notify: NotifyConfig,
+    #[serde(default)]
+    pub accounts: Vec<AccountConfig>,
+    #[serde(default)]
+    pub server: ServerConfig,
+    #[serde(default)]
+    pub influx: InfluxConfig,
+    #[serde(default)]
+    pub mqtt: MqttConfig,
+}
+
+fn default_server_bind() -> String {
+    "0.0.0.0:8080".to_string()
+}
+
+/// Embedded HTTP server exposing the recorded data as a JSON API.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ServerConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_server_bind")]
+    pub bind: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: default_server_bind(),
+        }
+    }
+}
+
+fn default_influx_measurement() -> String {
+    "uestc_power".to_string()
+}
+
+fn default_influx_batch_size() -> usize {
+    50
+}
+
+fn default_influx_flush_interval_seconds() -> u64 {
+    60
+}
+
+fn default_influx_max_buffer() -> usize {
+    10_000
+}
+
+/// Optional sink writing every sample as InfluxDB line protocol over HTTP.
+#[derive(Debug, Deserialize, Clone)]
+pub struct InfluxConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Full write endpoint, e.g. `http://host:8086/api/v2/write?org=o&bucket=b`
+    /// for InfluxDB 2.x or `http://host:8086/write?db=power` for 1.x.
+    #[serde(default)]
+    pub url: String,
+    /// Sent as `Authorization: Token <token>` when not empty.
+    #[serde(default)]
+    pub token: String,
+    #[serde(default = "default_influx_measurement")]
+    pub measurement: String,
+    #[serde(default = "default_influx_batch_size")]
+    pub batch_size: usize,
+    #[serde(default = "default_influx_flush_interval_seconds")]
+    pub flush_interval_seconds: u64,
+    /// Samples kept while the database is unreachable; the oldest are dropped first.
+    #[serde(default = "default_influx_max_buffer")]
+    pub max_buffer: usize,
+}
+
+impl Default for InfluxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            token: String::new(),
+            measurement: default_influx_measurement(),
+            batch_size: default_influx_batch_size(),
+            flush_interval_seconds: default_influx_flush_interval_seconds(),
+            max_buffer: default_influx_max_buffer(),
+        }
+    }
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_client_id() -> String {
+    "uestc-power-monitor".to_string()
+}
+
+fn default_mqtt_topic_prefix() -> String {
+    "uestc_power".to_string()
+}
+
+fn default_mqtt_discovery_enabled() -> bool {
+    true
+}
+
+fn default_mqtt_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+/// Optional MQTT publisher with Home Assistant discovery.
+#[derive(Debug, Deserialize, Clone)]
+pub struct MqttConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    #[serde(default = "default_mqtt_topic_prefix")]
+    pub topic_prefix: String,
+    #[serde(default = "default_mqtt_discovery_enabled")]
+    pub discovery_enabled: bool,
+    #[serde(default = "default_mqtt_discovery_prefix")]
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            port: default_mqtt_port(),
+            client_id: default_mqtt_client_id(),
+            username: String::new(),
+            password: String::new(),
+            topic_prefix: default_mqtt_topic_prefix(),
+            discovery_enabled: default_mqtt_discovery_enabled(),
+            discovery_prefix: default_mqtt_discovery_prefix(),
+        }
+    }
+}
+
+/// A monitored UESTC account. Each account is polled by its own `ApiService`
+/// and may override the global threshold and notification settings.
+#[derive(Debug, Deserialize, Clone)]
+pub struct AccountConfig {
+    #[serde(default)]
+    pub name: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    #[serde(default)]
+    pub login_type: LoginType,
+    #[serde(default)]
+    pub cookie_file: String,
+    #[serde(default)]
+    pub threshold: Option<f64>,
+    /// `[accounts.notify]`, the settings that differ from the global `[notify]`
+    #[serde(default)]
+    pub notify: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl AccountConfig {
+    /// Resolve the notification settings for this account: the global
+    /// `[notify]` section with the `[accounts.notify]` settings on top, and
+    /// the per-account threshold applied.
+    pub fn notify_config(&self, default: &NotifyConfig) -> Result<NotifyConfig, String> {
+        let mut config = match &self.notify {
+            Some(overrides) => {
+                let base = Config::try_from(default).map_err(|e| e.to_string())?;
+                let overrides = serde_json::to_string(overrides).map_err(|e| e.to_string())?;
+                Config::builder()
+                    .add_source(base)
+                    .add_source(File::from_str(&overrides, FileFormat::Json))
+                    .build()
+                    .and_then(Config::try_deserialize)
+                    .map_err(|e| format!("[{}] accounts.notify: {}", self.name, e))?
+            }
+            None => default.clone(),
+        };
+        if let Some(threshold) = self.threshold {
+            config.threshold = threshold;
+        }
+        Ok(config)
+    }
 }
 
 fn default_interval() -> u64 {
@@ -54,6 +241,42 @@ fn default_fetch_failure_cooldown_minutes() -> u64 {
     60 // 1 hour
 }
 
+fn default_recharge_enabled() -> bool {
+    true
+}
+
+fn default_forecast_window_days() -> u32 {
+    7 // one week of history
+}
+
+fn default_depletion_alert_days() -> f64 {
+    3.0 // 3 days
+}
+
+fn default_channel_timeout_seconds() -> u64 {
+    60 // 1 minute
+}
+
+fn default_outbox_max_age_minutes() -> u64 {
+    1440 // 1 day
+}
+
+fn default_quiet_pushover_priority() -> i8 {
+    -1 // quiet, no sound or vibration
+}
+
+fn default_quiet_ntfy_priority() -> u8 {
+    2 // low, no sound or vibration
+}
+
+fn default_quiet_bark_level() -> BarkLevel {
+    BarkLevel::Passive // added to the list without lighting up the screen
+}
+
+fn default_quiet_gotify_priority() -> u8 {
+    3 // shown without a sound on Android
+}
+
 fn default_pushover_priority() -> i8 {
     0
 }
@@ -66,6 +289,10 @@ fn default_pushover_expire() -> u32 {
     3600
 }
 
+fn default_webhook_method() -> String {
+    "POST".to_string()
+}
+
 fn default_ntfy_priority() -> u8 {
     3
 }
@@ -74,6 +301,14 @@ fn default_ntfy_use_markdown() -> bool {
     true
 }
 
+fn default_gotify_priority() -> u8 {
+    5
+}
+
+fn default_bark_server_url() -> String {
+    "https://api.day.app".to_string()
+}
+
 fn default_smtp_port() -> u16 {
     587 // Default to STARTTLS port
 }
@@ -82,7 +317,7 @@ fn default_smtp_encryption() -> SmtpEncryption {
     SmtpEncryption::Starttls
 }
 
-#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum SmtpEncryption {
     #[default]
@@ -91,7 +326,172 @@ pub enum SmtpEncryption {
     None, // No encryption (for testing/internal servers)
 }
 
-#[derive(Debug, Deserialize, Clone, Default)]
+/// One step of an escalating low balance alert, e.g. 20 CNY as info, 10 as
+/// warning and 3 as critical.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ThresholdTier {
+    pub threshold: f64,
+    #[serde(default)]
+    pub level: AlertLevel,
+    // Defaults to notify.cooldown_minutes
+    #[serde(default)]
+    pub cooldown_minutes: Option<u64>,
+    // Override the priority implied by the level
+    #[serde(default)]
+    pub pushover_priority: Option<i8>,
+    #[serde(default)]
+    pub ntfy_priority: Option<u8>,
+    // Channels to notify, all when empty
+    #[serde(default)]
+    pub channels: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertLevel {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl AlertLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertLevel::Info => "info",
+            AlertLevel::Warning => "warning",
+            AlertLevel::Critical => "critical",
+        }
+    }
+
+    pub fn pushover_priority(&self) -> i8 {
+        match self {
+            AlertLevel::Info => 0,
+            AlertLevel::Warning => 1,
+            AlertLevel::Critical => 2,
+        }
+    }
+
+    pub fn ntfy_priority(&self) -> u8 {
+        match self {
+            AlertLevel::Info => 3,
+            AlertLevel::Warning => 4,
+            AlertLevel::Critical => 5,
+        }
+    }
+
+    pub fn gotify_priority(&self) -> u8 {
+        match self {
+            AlertLevel::Info => 5,
+            AlertLevel::Warning => 7,
+            AlertLevel::Critical => 10,
+        }
+    }
+
+    pub fn bark_level(&self) -> BarkLevel {
+        match self {
+            AlertLevel::Info => BarkLevel::Active,
+            AlertLevel::Warning => BarkLevel::TimeSensitive,
+            AlertLevel::Critical => BarkLevel::Critical,
+        }
+    }
+}
+
+/// Interruption level of a Bark notification, from the least to the most
+/// intrusive. `critical` plays a sound even when the phone is muted.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum BarkLevel {
+    Passive,
+    #[default]
+    Active,
+    TimeSensitive,
+    Critical,
+}
+
+/// A daily do-not-disturb window, e.g. 23:00 to 07:00, in local time.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    // What happens to events during the window, critical ones included
+    #[serde(default)]
+    pub mode: QuietMode,
+    // Leave critical low balance alerts alone in both modes
+    #[serde(default)]
+    pub allow_critical: bool,
+    // Highest priority during the window in downgrade mode
+    #[serde(default = "default_quiet_pushover_priority")]
+    pub pushover_priority: i8,
+    #[serde(default = "default_quiet_ntfy_priority")]
+    pub ntfy_priority: u8,
+    #[serde(default = "default_quiet_bark_level")]
+    pub bark_level: BarkLevel,
+    #[serde(default = "default_quiet_gotify_priority")]
+    pub gotify_priority: u8,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum QuietMode {
+    /// Hold the notification until the window ends
+    #[default]
+    Defer,
+    /// Send it right away with the lowered priority
+    Downgrade,
+}
+
+impl QuietHours {
+    /// Whether `time` falls in the window. The window may span midnight and
+    /// is empty when `start` equals `end`.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// Whether a notification at `time` is held until the window ends.
+    pub fn defers(&self, time: NaiveTime, critical: bool) -> bool {
+        self.applies(QuietMode::Defer, time, critical)
+    }
+
+    /// Whether a notification at `time` is sent with its priority lowered.
+    pub fn downgrades(&self, time: NaiveTime, critical: bool) -> bool {
+        self.applies(QuietMode::Downgrade, time, critical)
+    }
+
+    /// Critical alerts are only exempt with `allow_critical`, whatever the
+    /// mode, so the legacy single `threshold` (a critical tier) is quiet too.
+    fn applies(&self, mode: QuietMode, time: NaiveTime, critical: bool) -> bool {
+        self.mode == mode && !(critical && self.allow_critical) && self.contains(time)
+    }
+
+    /// The first end of the window after `now`.
+    pub fn next_end(&self, now: DateTime<Local>) -> DateTime<Local> {
+        let mut end = now.date_naive().and_time(self.end);
+        if end <= now.naive_local() {
+            end += chrono::Duration::days(1);
+        }
+        end.and_local_timezone(Local)
+            .earliest()
+            .unwrap_or_else(|| now + chrono::Duration::hours(1))
+    }
+}
+
+/// Language of the built-in notification messages.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+pub enum Locale {
+    #[default]
+    #[serde(rename = "en")]
+    En,
+    #[serde(rename = "zh-CN", alias = "zh-cn", alias = "zh_CN", alias = "zh")]
+    ZhCn,
+}
+
+// Serialize is only used to derive `[[notify.channels]]` configs from it
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct NotifyConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -99,6 +499,9 @@ pub struct NotifyConfig {
     pub threshold: f64,
     #[serde(default = "default_cooldown_minutes")]
     pub cooldown_minutes: u64,
+    // Escalating low balance thresholds, replaces `threshold` when set
+    #[serde(default)]
+    pub threshold_tiers: Vec<ThresholdTier>,
     #[serde(default)]
     pub heartbeat_enabled: bool,
     #[serde(default = "default_heartbeat_hour")]
@@ -111,12 +514,42 @@ pub struct NotifyConfig {
     pub fetch_failure_threshold: u32,
     #[serde(default = "default_fetch_failure_cooldown_minutes")]
     pub fetch_failure_cooldown_minutes: u64,
+    #[serde(default = "default_recharge_enabled")]
+    pub recharge_enabled: bool,
+    #[serde(default = "default_forecast_window_days")]
+    pub forecast_window_days: u32,
+    #[serde(default)]
+    pub depletion_alert_enabled: bool,
+    #[serde(default = "default_depletion_alert_days")]
+    pub depletion_alert_days: f64,
+    // Do-not-disturb window, can be set per channel
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    // Time limit per channel for sending a notification, retries included
+    #[serde(default = "default_channel_timeout_seconds")]
+    pub channel_timeout_seconds: u64,
+    // Failed notifications are redelivered until they are this old, 0 disables
+    #[serde(default = "default_outbox_max_age_minutes")]
+    pub outbox_max_age_minutes: u64,
     #[serde(default)]
     pub notify_type: NotifyType, // Keep for backward compatibility
     #[serde(default)]
     pub notify_types: Vec<NotifyType>, // New: support multiple channels
+    // Named channels, replace notify_type(s) when set
+    #[serde(default, skip_serializing)]
+    pub channels: Vec<ChannelConfig>,
+    // Webhook configuration, the body is a versioned JSON payload unless a
+    // body template is set and is signed when a secret is set
     #[serde(default)]
     pub webhook_url: String,
+    #[serde(default = "default_webhook_method")]
+    pub webhook_method: String,
+    #[serde(default)]
+    pub webhook_headers: HashMap<String, String>,
+    #[serde(default)]
+    pub webhook_body_template: String,
+    #[serde(default)]
+    pub webhook_secret: String,
     #[serde(default)]
     pub telegram_bot_token: String,
     #[serde(default)]
@@ -166,9 +599,104 @@ pub struct NotifyConfig {
     pub smtp_to: String, // Comma-separated list of recipients
     #[serde(default = "default_smtp_encryption")]
     pub smtp_encryption: SmtpEncryption,
+    // Discord / Slack incoming webhooks
+    #[serde(default)]
+    pub discord_webhook_url: String,
+    #[serde(default)]
+    pub slack_webhook_url: String,
+    // WeCom (企业微信) group robot
+    #[serde(default)]
+    pub wecom_webhook_url: String,
+    // DingTalk custom robot, requests are signed when a secret is set
+    #[serde(default)]
+    pub dingtalk_webhook_url: String,
+    #[serde(default)]
+    pub dingtalk_secret: String,
+    // Feishu / Lark bot, requests are signed when a secret is set
+    #[serde(default)]
+    pub feishu_webhook_url: String,
+    #[serde(default)]
+    pub feishu_secret: String,
+    // ServerChan (Server酱) SendKey
+    #[serde(default)]
+    pub serverchan_sendkey: String,
+    // PushPlus token, the topic pushes to a group instead of the token owner
+    #[serde(default)]
+    pub pushplus_token: String,
+    #[serde(default)]
+    pub pushplus_topic: String,
+    // Bark configuration
+    #[serde(default = "default_bark_server_url")]
+    pub bark_server_url: String,
+    #[serde(default)]
+    pub bark_device_key: String,
+    #[serde(default)]
+    pub bark_group: String,
+    #[serde(default)]
+    pub bark_sound: String,
+    #[serde(default)]
+    pub bark_level: BarkLevel,
+    // Gotify configuration
+    #[serde(default)]
+    pub gotify_server_url: String,
+    #[serde(default)]
+    pub gotify_app_token: String,
+    #[serde(default = "default_gotify_priority")]
+    pub gotify_priority: u8,
+    // Apprise API server, notifying `apprise_urls` or else the URLs stored
+    // under `apprise_config_key`
+    #[serde(default)]
+    pub apprise_api_url: String,
+    #[serde(default)]
+    pub apprise_urls: Vec<String>,
+    #[serde(default)]
+    pub apprise_config_key: String,
+    #[serde(default)]
+    pub apprise_tag: String,
+    #[serde(default)]
+    pub locale: Locale,
+    // Channels per event name, events without a route go to every channel
+    #[serde(default)]
+    pub routes: HashMap<String, Vec<String>>,
+    // Message templates, keyed by event name (e.g. "low_balance")
+    #[serde(default)]
+    pub templates: HashMap<String, MessageTemplate>,
+    // Per channel overrides, keyed by notify type and then event name
+    #[serde(default)]
+    pub channel_templates: HashMap<String, HashMap<String, MessageTemplate>>,
+}
+
+/// A named channel from `[[notify.channels]]`. Settings that are not given
+/// are inherited from `[notify]`, so several channels can share e.g. a bot
+/// token and differ only in the chat id.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ChannelConfig {
+    // Defaults to the notify type
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "type")]
+    pub notify_type: NotifyType,
+    #[serde(flatten)]
+    pub settings: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A notification channel with the settings its notifier is built from.
+#[derive(Debug, Clone)]
+pub struct NotifyChannel {
+    pub name: String,
+    pub notify_type: NotifyType,
+    pub config: NotifyConfig,
+}
+
+/// A user defined notification message. Missing parts fall back to the
+/// built-in text.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct MessageTemplate {
+    pub title: Option<String>,
+    pub body: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum NotifyType {
     #[default]
@@ -178,9 +706,128 @@ pub enum NotifyType {
     Pushover,
     Ntfy,
     Email,
+    Discord,
+    Slack,
+    WeCom,
+    DingTalk,
+    #[serde(alias = "lark")]
+    Feishu,
+    ServerChan,
+    PushPlus,
+    Bark,
+    Gotify,
+    Apprise,
+}
+
+impl NotifyType {
+    pub const ALL: [NotifyType; 16] = [
+        NotifyType::Console,
+        NotifyType::Webhook,
+        NotifyType::Telegram,
+        NotifyType::Pushover,
+        NotifyType::Ntfy,
+        NotifyType::Email,
+        NotifyType::Discord,
+        NotifyType::Slack,
+        NotifyType::WeCom,
+        NotifyType::DingTalk,
+        NotifyType::Feishu,
+        NotifyType::ServerChan,
+        NotifyType::PushPlus,
+        NotifyType::Bark,
+        NotifyType::Gotify,
+        NotifyType::Apprise,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotifyType::Console => "console",
+            NotifyType::Webhook => "webhook",
+            NotifyType::Telegram => "telegram",
+            NotifyType::Pushover => "pushover",
+            NotifyType::Ntfy => "ntfy",
+            NotifyType::Email => "email",
+            NotifyType::Discord => "discord",
+            NotifyType::Slack => "slack",
+            NotifyType::WeCom => "wecom",
+            NotifyType::DingTalk => "dingtalk",
+            NotifyType::Feishu => "feishu",
+            NotifyType::ServerChan => "serverchan",
+            NotifyType::PushPlus => "pushplus",
+            NotifyType::Bark => "bark",
+            NotifyType::Gotify => "gotify",
+            NotifyType::Apprise => "apprise",
+        }
+    }
 }
 
 impl NotifyConfig {
+    /// Low balance tiers ordered from the highest threshold to the lowest.
+    /// Without `threshold_tiers`, `threshold` acts as a single critical tier.
+    pub fn low_balance_tiers(&self) -> Vec<ThresholdTier> {
+        let mut tiers = if self.threshold_tiers.is_empty() {
+            vec![ThresholdTier {
+                threshold: self.threshold,
+                level: AlertLevel::Critical,
+                cooldown_minutes: None,
+                pushover_priority: None,
+                ntfy_priority: None,
+                channels: Vec::new(),
+            }]
+        } else {
+            self.threshold_tiers.clone()
+        };
+        tiers.sort_by(|a, b| b.threshold.total_cmp(&a.threshold));
+        tiers
+    }
+
+    /// Resolves the channels to notify. Without `[[notify.channels]]`, each
+    /// active notify type is a channel named after the type.
+    pub fn channels(&self) -> Result<Vec<NotifyChannel>, String> {
+        if self.channels.is_empty() {
+            return Ok(self
+                .get_active_notify_types()
+                .into_iter()
+                .map(|notify_type| NotifyChannel {
+                    name: notify_type.as_str().to_string(),
+                    notify_type,
+                    config: self.clone(),
+                })
+                .collect());
+        }
+
+        let base = Config::try_from(self).map_err(|e| e.to_string())?;
+        let mut channels: Vec<NotifyChannel> = Vec::new();
+        for channel in &self.channels {
+            let name = if channel.name.is_empty() {
+                channel.notify_type.as_str().to_string()
+            } else {
+                channel.name.clone()
+            };
+            if channels.iter().any(|c| c.name == name) {
+                return Err(format!(
+                    "notify.channels: duplicate channel name '{}'",
+                    name
+                ));
+            }
+
+            // The channel settings override the shared [notify] settings
+            let settings = serde_json::to_string(&channel.settings).map_err(|e| e.to_string())?;
+            let config = Config::builder()
+                .add_source(base.clone())
+                .add_source(File::from_str(&settings, FileFormat::Json))
+                .build()
+                .and_then(Config::try_deserialize)
+                .map_err(|e| format!("notify.channels '{}': {}", name, e))?;
+            channels.push(NotifyChannel {
+                name,
+                notify_type: channel.notify_type.clone(),
+                config,
+            });
+        }
+        Ok(channels)
+    }
+
     pub fn get_active_notify_types(&self) -> Vec<NotifyType> {
         if !self.notify_types.is_empty() {
             self.notify_types.clone()
@@ -191,6 +838,44 @@ impl NotifyConfig {
 }
 
 impl AppConfig {
+    /// Returns the accounts to monitor. When no `[[accounts]]` are configured,
+    /// the top-level `username`/`password`/`cookie_file` act as a single account.
+    pub fn get_accounts(&self) -> Vec<AccountConfig> {
+        if self.accounts.is_empty() {
+            return vec![AccountConfig {
+                name: self
+                    .username
+                    .clone()
+                    .unwrap_or_else(|| "default".to_string()),
+                username: self.username.clone(),
+                password: self.password.clone(),
+                login_type: self.login_type,
+                cookie_file: self.cookie_file.clone(),
+                threshold: None,
+                notify: None,
+            }];
+        }
+
+        self.accounts
+            .iter()
+            .enumerate()
+            .map(|(idx, account)| {
+                let mut account = account.clone();
+                if account.name.trim().is_empty() {
+                    account.name = account
+                        .username
+                        .clone()
+                        .unwrap_or_else(|| format!("account{}", idx + 1));
+                }
+                if account.cookie_file.trim().is_empty() {
+                    // Separate cookie jars so accounts do not overwrite each other's session
+                    account.cookie_file = format!("uestc_cookies_{}.json", account.name);
+                }
+                account
+            })
+            .collect()
+    }
+
     pub fn new() -> Result<Self, ConfigError> {
         let mut builder = Config::builder();
 
@@ -206,6 +891,8 @@ impl AppConfig {
             ("password", "/run/secrets/password"),
             ("service_url", "/run/secrets/service_url"),
             ("database_url", "/run/secrets/database_url"),
+            ("influx.token", "/run/secrets/influx_token"),
+            ("mqtt.password", "/run/secrets/mqtt_password"),
         ];
 
         let mut secrets_map = std::collections::HashMap::new();
@@ -242,3 +929,224 @@ impl AppConfig {
         builder.build()?.try_deserialize()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn quiet_hours(start: NaiveTime, end: NaiveTime) -> QuietHours {
+        QuietHours {
+            start,
+            end,
+            mode: QuietMode::Defer,
+            allow_critical: false,
+            pushover_priority: default_quiet_pushover_priority(),
+            ntfy_priority: default_quiet_ntfy_priority(),
+            bark_level: default_quiet_bark_level(),
+            gotify_priority: default_quiet_gotify_priority(),
+        }
+    }
+
+    fn local(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2025, 1, day, hour, minute, 0)
+            .earliest()
+            .unwrap()
+    }
+
+    #[test]
+    fn quiet_hours_within_a_day() {
+        let quiet = quiet_hours(time(9, 0), time(17, 0));
+        assert!(quiet.contains(time(9, 0)));
+        assert!(quiet.contains(time(16, 59)));
+        assert!(!quiet.contains(time(17, 0)));
+        assert!(!quiet.contains(time(8, 59)));
+    }
+
+    #[test]
+    fn quiet_hours_spanning_midnight() {
+        let quiet = quiet_hours(time(23, 0), time(7, 0));
+        assert!(quiet.contains(time(23, 0)));
+        assert!(quiet.contains(time(0, 0)));
+        assert!(quiet.contains(time(6, 59)));
+        assert!(!quiet.contains(time(7, 0)));
+        assert!(!quiet.contains(time(12, 0)));
+        assert!(!quiet.contains(time(22, 59)));
+    }
+
+    #[test]
+    fn quiet_hours_empty_when_start_equals_end() {
+        let quiet = quiet_hours(time(8, 0), time(8, 0));
+        assert!(!quiet.contains(time(8, 0)));
+        assert!(!quiet.contains(time(0, 0)));
+        assert!(!quiet.contains(time(20, 0)));
+    }
+
+    #[test]
+    fn quiet_hours_next_end_spanning_midnight() {
+        let quiet = quiet_hours(time(23, 0), time(7, 0));
+        // before midnight the window ends the next morning
+        assert_eq!(quiet.next_end(local(15, 23, 30)), local(16, 7, 0));
+        // after midnight it ends the same morning
+        assert_eq!(quiet.next_end(local(16, 2, 0)), local(16, 7, 0));
+        // at the end itself the next end is a day later
+        assert_eq!(quiet.next_end(local(16, 7, 0)), local(17, 7, 0));
+    }
+
+    #[test]
+    fn quiet_hours_next_end_when_start_equals_end() {
+        let quiet = quiet_hours(time(8, 0), time(8, 0));
+        assert_eq!(quiet.next_end(local(15, 6, 0)), local(15, 8, 0));
+        assert_eq!(quiet.next_end(local(15, 8, 0)), local(16, 8, 0));
+    }
+
+    #[test]
+    fn quiet_hours_downgrade_only_in_downgrade_mode() {
+        let mut quiet = quiet_hours(time(23, 0), time(7, 0));
+        assert!(quiet.defers(time(1, 0), false));
+        assert!(!quiet.downgrades(time(1, 0), false));
+        quiet.mode = QuietMode::Downgrade;
+        assert!(!quiet.defers(time(1, 0), false));
+        assert!(quiet.downgrades(time(1, 0), false));
+        assert!(!quiet.downgrades(time(12, 0), false));
+    }
+
+    #[test]
+    fn quiet_hours_treat_critical_alike_in_both_modes() {
+        let mut quiet = quiet_hours(time(23, 0), time(7, 0));
+        assert!(quiet.defers(time(3, 0), true));
+        quiet.mode = QuietMode::Downgrade;
+        assert!(quiet.downgrades(time(3, 0), true));
+
+        quiet.allow_critical = true;
+        assert!(!quiet.downgrades(time(3, 0), true));
+        assert!(quiet.downgrades(time(3, 0), false));
+        quiet.mode = QuietMode::Defer;
+        assert!(!quiet.defers(time(3, 0), true));
+        assert!(quiet.defers(time(3, 0), false));
+    }
+
+    #[test]
+    fn channels_inherit_notify_settings() {
+        let config: NotifyConfig = Config::builder()
+            .add_source(File::from_str(
+                r#"
+                enabled = true
+                telegram_bot_token = "token"
+                telegram_chat_id = "000"
+                webhook_headers = { Authorization = "Bearer abc" }
+                quiet_hours = { start = "23:00:00", end = "07:00:00" }
+                threshold_tiers = [{ threshold = 10.0, level = "warning", channels = ["alice"] }]
+
+                [[channels]]
+                name = "alice"
+                type = "telegram"
+                telegram_chat_id = "111"
+
+                [[channels]]
+                type = "webhook"
+                webhook_url = "https://example.com/hook"
+                "#,
+                FileFormat::Toml,
+            ))
+            .build()
+            .and_then(Config::try_deserialize)
+            .unwrap();
+
+        let channels = config.channels().unwrap();
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[0].name, "alice");
+        assert_eq!(channels[0].config.telegram_bot_token, "token");
+        assert_eq!(channels[0].config.telegram_chat_id, "111");
+        assert_eq!(channels[1].name, "webhook");
+        assert_eq!(channels[1].config.webhook_url, "https://example.com/hook");
+        assert_eq!(
+            channels[1].config.webhook_headers["Authorization"],
+            "Bearer abc"
+        );
+        assert_eq!(channels[1].config.quiet_hours, config.quiet_hours);
+        assert_eq!(channels[1].config.threshold_tiers.len(), 1);
+    }
+
+    #[test]
+    fn account_notify_overrides_the_global_settings() {
+        let config: AppConfig = Config::builder()
+            .add_source(File::from_str(
+                r#"
+                database_url = "sqlite::memory:"
+
+                [notify]
+                enabled = true
+                notify_types = ["telegram", "webhook"]
+                telegram_bot_token = "token"
+                telegram_chat_id = "000"
+                cooldown_minutes = 30
+                webhook_headers = { Authorization = "Bearer abc" }
+
+                [[accounts]]
+                name = "alice"
+                threshold = 8.0
+
+                [accounts.notify]
+                notify_types = ["telegram"]
+                telegram_chat_id = "111"
+                webhook_headers = { "X-Room" = "101" }
+
+                [[accounts]]
+                name = "bob"
+
+                [[accounts]]
+                name = "carol"
+
+                [accounts.notify]
+                cooldown_minutes = "soon"
+                "#,
+                FileFormat::Toml,
+            ))
+            .build()
+            .and_then(Config::try_deserialize)
+            .unwrap();
+
+        let alice = config.accounts[0].notify_config(&config.notify).unwrap();
+        assert!(alice.enabled);
+        assert_eq!(alice.telegram_bot_token, "token");
+        assert_eq!(alice.telegram_chat_id, "111");
+        assert_eq!(alice.cooldown_minutes, 30);
+        assert_eq!(alice.threshold, 8.0);
+        assert_eq!(alice.webhook_headers["Authorization"], "Bearer abc");
+        assert_eq!(alice.webhook_headers["X-Room"], "101");
+        assert_eq!(alice.notify_types, [NotifyType::Telegram]);
+
+        let bob = config.accounts[1].notify_config(&config.notify).unwrap();
+        assert_eq!(bob.telegram_chat_id, "000");
+        assert_eq!(bob.threshold, config.notify.threshold);
+        assert_eq!(bob.notify_types.len(), 2);
+
+        assert!(config.accounts[2].notify_config(&config.notify).is_err());
+    }
+
+    #[test]
+    fn channels_reject_duplicate_names() {
+        let config = NotifyConfig {
+            channels: vec![
+                ChannelConfig {
+                    name: String::new(),
+                    notify_type: NotifyType::Webhook,
+                    settings: serde_json::Map::new(),
+                },
+                ChannelConfig {
+                    name: "webhook".to_string(),
+                    notify_type: NotifyType::Webhook,
+                    settings: serde_json::Map::new(),
+                },
+            ],
+            ..Default::default()
+        };
+        assert!(config.channels().is_err());
+    }
+}
@@ -0,0 +1,429 @@
+mod mysql;
+mod postgres;
+mod sqlite;
+
+use crate::api::PowerInfo;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tracing::debug;
+
+pub use mysql::MySqlStorage;
+pub use postgres::PostgresStorage;
+pub use sqlite::SqliteStorage;
+
+/// A row of the `power_records` table.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PowerRecord {
+    pub id: i64,
+    pub remaining_energy: f64,
+    pub remaining_money: f64,
+    pub meter_room_id: String,
+    pub room_display_name: String,
+    pub room_id: String,
+    pub building_id: String,
+    pub campus_id: String,
+    pub room_number: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A room that has at least one record, with the time span it was observed.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct RoomSummary {
+    pub room_id: String,
+    pub room_display_name: String,
+    pub building_id: String,
+    pub campus_id: String,
+    pub room_number: String,
+    pub record_count: i64,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// A row of the `recharges` table, recorded when the balance jumps up.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct RechargeRecord {
+    pub id: i64,
+    pub room_id: String,
+    pub room_display_name: String,
+    pub amount: f64,
+    pub previous_money: f64,
+    pub current_money: f64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A row of the `notifications` table: one delivery attempt of an event to a
+/// channel, including its retries.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct NotificationRecord {
+    pub id: i64,
+    pub account: String,
+    pub event: String,
+    /// Channel name, see `[[notify.channels]]`
+    pub channel: String,
+    /// Notify type of the channel
+    pub channel_type: String,
+    pub success: bool,
+    /// Short failure category (`timeout`, `connect`, `http_503`, ...), no details
+    pub error_class: Option<String>,
+    pub retries: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A notification attempt to insert into the `notifications` table.
+#[derive(Debug, Clone)]
+pub struct NotificationAttempt<'a> {
+    pub account: &'a str,
+    pub event: &'a str,
+    pub channel: &'a str,
+    pub channel_type: &'a str,
+    /// `None` when the notification was delivered
+    pub error_class: Option<&'a str>,
+    pub retries: i64,
+}
+
+/// A row of the `notification_outbox` table: a notification that could not be
+/// delivered and waits to be sent again, one held back by quiet hours or one
+/// still waiting for the dispatcher.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub account: String,
+    pub channel: String,
+    pub event: String,
+    /// JSON of what to send, see `notify::NotificationPayload`
+    pub payload: String,
+    /// Redelivery attempts so far
+    pub attempts: i64,
+    /// Last error, none for notifications not attempted yet (deferred by
+    /// quiet hours or waiting for the dispatcher)
+    pub error_class: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    /// The entry is dropped when it has not been delivered by then
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A notification to insert into the `notification_outbox` table.
+#[derive(Debug, Clone)]
+pub struct NewOutboxEntry<'a> {
+    pub account: &'a str,
+    pub channel: &'a str,
+    pub event: &'a str,
+    pub payload: &'a str,
+    pub error_class: Option<&'a str>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A row of the `notification_state` table: what the notification manager of
+/// an account has to remember across restarts.
+#[derive(Debug, Clone, PartialEq, Serialize, FromRow)]
+pub struct NotificationState {
+    pub account: String,
+    /// Threshold of the low balance tier the balance was in at the last check
+    pub low_balance_threshold: Option<f64>,
+    pub last_low_balance_notify_at: Option<DateTime<Utc>>,
+    /// Local date of the last heartbeat
+    pub last_heartbeat_date: Option<NaiveDate>,
+    pub consecutive_fetch_failures: i64,
+    pub last_fetch_failure_notify_at: Option<DateTime<Utc>>,
+    pub last_depletion_notify_at: Option<DateTime<Utc>>,
+}
+
+/// A versioned schema change. Every backend ships its own list, applied in
+/// order at startup and tracked in the `schema_version` table.
+/// Released migrations must never change; add a new one instead.
+struct Migration {
+    version: i64,
+    description: &'static str,
+    sql: &'static str,
+}
+
+pub type StorageFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, Box<dyn Error>>> + Send + 'a>>;
+
+/// A database backend for power records and recharges.
+///
+/// Time range filters are inclusive, and list queries return rows ordered
+/// from oldest to newest, keeping the newest `limit` rows.
+pub trait Storage: Send + Sync {
+    /// Prepares the connection and applies pending migrations.
+    fn init(&self) -> StorageFuture<'_, ()>;
+
+    fn save_data<'a>(&'a self, data: &'a PowerInfo) -> StorageFuture<'a, ()>;
+
+    fn save_recharge<'a>(
+        &'a self,
+        data: &'a PowerInfo,
+        amount: f64,
+        previous_money: f64,
+    ) -> StorageFuture<'a, ()>;
+
+    /// Returns the most recent record of a room, if any.
+    fn latest_record<'a>(&'a self, room_id: &'a str) -> StorageFuture<'a, Option<PowerRecord>>;
+
+    /// Returns the most recent record of every room.
+    fn latest_records(&self) -> StorageFuture<'_, Vec<PowerRecord>>;
+
+    fn history<'a>(
+        &'a self,
+        room_id: Option<&'a str>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<PowerRecord>>;
+
+    /// Returns every room that has been recorded.
+    fn rooms(&self) -> StorageFuture<'_, Vec<RoomSummary>>;
+
+    fn recharges<'a>(
+        &'a self,
+        room_id: Option<&'a str>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<RechargeRecord>>;
+
+    fn save_notification<'a>(
+        &'a self,
+        attempt: &'a NotificationAttempt<'a>,
+    ) -> StorageFuture<'a, ()>;
+
+    /// Returns notification attempts, optionally filtered by account and event.
+    fn notifications<'a>(
+        &'a self,
+        account: Option<&'a str>,
+        event: Option<&'a str>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<NotificationRecord>>;
+
+    /// Adds an entry to the outbox, returning its id.
+    fn enqueue_outbox<'a>(&'a self, entry: &'a NewOutboxEntry<'a>) -> StorageFuture<'a, i64>;
+
+    /// Returns the outbox entries of an account that are due at `now`,
+    /// oldest first.
+    fn due_outbox<'a>(
+        &'a self,
+        account: &'a str,
+        now: DateTime<Utc>,
+    ) -> StorageFuture<'a, Vec<OutboxEntry>>;
+
+    /// Records a failed redelivery and schedules the next one.
+    fn reschedule_outbox<'a>(
+        &'a self,
+        id: i64,
+        attempts: i64,
+        error_class: &'a str,
+        next_attempt_at: DateTime<Utc>,
+    ) -> StorageFuture<'a, ()>;
+
+    fn delete_outbox(&self, id: i64) -> StorageFuture<'_, ()>;
+
+    /// Returns the saved notification state of an account, if any.
+    fn notification_state<'a>(
+        &'a self,
+        account: &'a str,
+    ) -> StorageFuture<'a, Option<NotificationState>>;
+
+    /// Inserts or replaces the notification state of `state.account`.
+    fn save_notification_state<'a>(&'a self, state: &'a NotificationState)
+    -> StorageFuture<'a, ()>;
+}
+
+fn url_scheme(database_url: &str) -> String {
+    database_url
+        .split_once(':')
+        .map(|(scheme, _)| scheme.to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+/// Returns whether `database_url` selects one of the supported backends.
+pub fn is_supported_url(database_url: &str) -> bool {
+    matches!(
+        url_scheme(database_url).as_str(),
+        "sqlite" | "postgres" | "postgresql" | "mysql" | "mariadb"
+    )
+}
+
+/// Shared handle to the storage backend selected by `database_url`.
+#[derive(Clone)]
+pub struct DbService {
+    storage: Arc<dyn Storage>,
+}
+
+impl DbService {
+    /// Connects to the backend matching the URL scheme:
+    /// `sqlite://`, `postgres://` / `postgresql://` or `mysql://` / `mariadb://`.
+    pub async fn new(database_url: String) -> Result<Self, Box<dyn Error>> {
+        let scheme = url_scheme(&database_url);
+        // Only log the scheme, the URL may contain credentials
+        debug!("Creating database connection pool for scheme: {}", scheme);
+
+        let storage: Arc<dyn Storage> = match scheme.as_str() {
+            "sqlite" => Arc::new(SqliteStorage::connect(&database_url).await?),
+            "postgres" | "postgresql" => Arc::new(PostgresStorage::connect(&database_url).await?),
+            "mysql" | "mariadb" => Arc::new(MySqlStorage::connect(&database_url).await?),
+            _ => {
+                return Err(format!(
+                    "Unsupported database_url scheme '{}': expected sqlite, postgres or mysql",
+                    scheme
+                )
+                .into());
+            }
+        };
+        debug!("Database connection pool created successfully");
+
+        Ok(Self { storage })
+    }
+
+    pub async fn init(&self) -> Result<(), Box<dyn Error>> {
+        self.storage.init().await
+    }
+
+    pub async fn save_data(&self, data: &PowerInfo) -> Result<(), Box<dyn Error>> {
+        debug!(
+            "Saving data to database: room={}, money={:.2}, energy={:.2}",
+            data.room_display_name, data.remaining_money, data.remaining_energy
+        );
+        self.storage.save_data(data).await?;
+        debug!("Data saved successfully to database");
+        Ok(())
+    }
+
+    pub async fn save_recharge(
+        &self,
+        data: &PowerInfo,
+        amount: f64,
+        previous_money: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        debug!(
+            "Saving recharge to database: room={}, amount={:.2}",
+            data.room_display_name, amount
+        );
+        self.storage
+            .save_recharge(data, amount, previous_money)
+            .await?;
+        debug!("Recharge saved successfully to database");
+        Ok(())
+    }
+
+    pub async fn latest_record(
+        &self,
+        room_id: &str,
+    ) -> Result<Option<PowerRecord>, Box<dyn Error>> {
+        self.storage.latest_record(room_id).await
+    }
+
+    pub async fn latest_records(&self) -> Result<Vec<PowerRecord>, Box<dyn Error>> {
+        self.storage.latest_records().await
+    }
+
+    /// Returns records ordered from oldest to newest, optionally filtered by
+    /// room and by a time range (both bounds inclusive).
+    pub async fn history(
+        &self,
+        room_id: Option<&str>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> Result<Vec<PowerRecord>, Box<dyn Error>> {
+        self.storage.history(room_id, from, to, limit).await
+    }
+
+    pub async fn rooms(&self) -> Result<Vec<RoomSummary>, Box<dyn Error>> {
+        self.storage.rooms().await
+    }
+
+    /// Returns recharges ordered from oldest to newest, filtered like [`Self::history`].
+    pub async fn recharges(
+        &self,
+        room_id: Option<&str>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> Result<Vec<RechargeRecord>, Box<dyn Error>> {
+        self.storage.recharges(room_id, from, to, limit).await
+    }
+
+    pub async fn save_notification(
+        &self,
+        attempt: &NotificationAttempt<'_>,
+    ) -> Result<(), Box<dyn Error>> {
+        debug!(
+            "Saving notification to database: event={}, channel={}, success={}",
+            attempt.event,
+            attempt.channel,
+            attempt.error_class.is_none()
+        );
+        self.storage.save_notification(attempt).await
+    }
+
+    /// Returns notification attempts ordered from oldest to newest, filtered
+    /// like [`Self::history`].
+    pub async fn notifications(
+        &self,
+        account: Option<&str>,
+        event: Option<&str>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> Result<Vec<NotificationRecord>, Box<dyn Error>> {
+        self.storage
+            .notifications(account, event, from, to, limit)
+            .await
+    }
+
+    pub async fn enqueue_outbox(&self, entry: &NewOutboxEntry<'_>) -> Result<i64, Box<dyn Error>> {
+        debug!(
+            "Adding notification to outbox: event={}, channel={}",
+            entry.event, entry.channel
+        );
+        self.storage.enqueue_outbox(entry).await
+    }
+
+    pub async fn due_outbox(
+        &self,
+        account: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<OutboxEntry>, Box<dyn Error>> {
+        self.storage.due_outbox(account, now).await
+    }
+
+    pub async fn reschedule_outbox(
+        &self,
+        id: i64,
+        attempts: i64,
+        error_class: &str,
+        next_attempt_at: DateTime<Utc>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.storage
+            .reschedule_outbox(id, attempts, error_class, next_attempt_at)
+            .await
+    }
+
+    pub async fn delete_outbox(&self, id: i64) -> Result<(), Box<dyn Error>> {
+        self.storage.delete_outbox(id).await
+    }
+
+    pub async fn notification_state(
+        &self,
+        account: &str,
+    ) -> Result<Option<NotificationState>, Box<dyn Error>> {
+        self.storage.notification_state(account).await
+    }
+
+    pub async fn save_notification_state(
+        &self,
+        state: &NotificationState,
+    ) -> Result<(), Box<dyn Error>> {
+        debug!("Saving notification state: account={}", state.account);
+        self.storage.save_notification_state(state).await
+    }
+}
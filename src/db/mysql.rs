@@ -0,0 +1,493 @@
+use super::{
+    Migration, NewOutboxEntry, NotificationAttempt, NotificationRecord, NotificationState,
+    OutboxEntry, PowerRecord, RechargeRecord, RoomSummary, Storage, StorageFuture,
+};
+use crate::api::PowerInfo;
+use chrono::{DateTime, Utc};
+use sqlx::mysql::MySqlPoolOptions;
+use sqlx::{MySql, Pool};
+use tracing::{debug, info};
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create power_records",
+        sql: include_str!("../../migrations/mysql/0001_create_power_records.sql"),
+    },
+    Migration {
+        version: 2,
+        description: "create recharges",
+        sql: include_str!("../../migrations/mysql/0002_create_recharges.sql"),
+    },
+    Migration {
+        version: 3,
+        description: "create notification_state",
+        sql: include_str!("../../migrations/mysql/0003_create_notification_state.sql"),
+    },
+    Migration {
+        version: 4,
+        description: "create notifications",
+        sql: include_str!("../../migrations/mysql/0004_create_notifications.sql"),
+    },
+    Migration {
+        version: 5,
+        description: "create notification_outbox",
+        sql: include_str!("../../migrations/mysql/0005_create_notification_outbox.sql"),
+    },
+];
+
+pub struct MySqlStorage {
+    pool: Pool<MySql>,
+}
+
+impl MySqlStorage {
+    pub async fn connect(database_url: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        // sqlx only understands the mysql:// scheme, MariaDB speaks the same protocol
+        let database_url = match database_url.split_once("://") {
+            Some((_, rest)) => format!("mysql://{}", rest),
+            None => database_url.to_string(),
+        };
+
+        let pool = MySqlPoolOptions::new()
+            .max_connections(5)
+            .connect(&database_url)
+            .await?;
+
+        Ok(Self { pool })
+    }
+
+    /// Applies every migration newer than the recorded schema version.
+    async fn migrate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_version (
+                version BIGINT NOT NULL PRIMARY KEY,
+                description VARCHAR(255) NOT NULL,
+                applied_at DATETIME(6) NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let current: i64 = sqlx::query_scalar(
+            "SELECT CAST(COALESCE(MAX(version), 0) AS SIGNED) FROM schema_version",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        debug!("Current schema version: {}", current);
+
+        for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+            info!(
+                "Applying migration {}: {}",
+                migration.version, migration.description
+            );
+            // MySQL commits DDL implicitly, so migrations are written to be
+            // re-runnable instead of relying on a transaction
+            sqlx::raw_sql(migration.sql).execute(&self.pool).await?;
+            sqlx::query(
+                "INSERT INTO schema_version (version, description, applied_at) VALUES (?, ?, ?)",
+            )
+            .bind(migration.version)
+            .bind(migration.description)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Storage for MySqlStorage {
+    fn init(&self) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            info!("Initializing DB (MySQL)...");
+            self.migrate().await?;
+            debug!("Database initialization completed");
+            Ok(())
+        })
+    }
+
+    fn save_data<'a>(&'a self, data: &'a PowerInfo) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            sqlx::query(
+                r#"
+                INSERT INTO power_records (
+                    remaining_energy, remaining_money, meter_room_id,
+                    room_display_name, room_id, building_id, campus_id, room_number,
+                    created_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(data.remaining_energy)
+            .bind(data.remaining_money)
+            .bind(&data.meter_room_id)
+            .bind(&data.room_display_name)
+            .bind(&data.room_id)
+            .bind(&data.building_id)
+            .bind(&data.campus_id)
+            .bind(&data.room_number)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn save_recharge<'a>(
+        &'a self,
+        data: &'a PowerInfo,
+        amount: f64,
+        previous_money: f64,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            sqlx::query(
+                r#"
+                INSERT INTO recharges (
+                    room_id, room_display_name, amount, previous_money, current_money,
+                    created_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&data.room_id)
+            .bind(&data.room_display_name)
+            .bind(amount)
+            .bind(previous_money)
+            .bind(data.remaining_money)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn latest_record<'a>(&'a self, room_id: &'a str) -> StorageFuture<'a, Option<PowerRecord>> {
+        Box::pin(async move {
+            let record = sqlx::query_as::<_, PowerRecord>(
+                r#"
+                SELECT * FROM power_records
+                WHERE room_id = ?
+                ORDER BY id DESC
+                LIMIT 1
+                "#,
+            )
+            .bind(room_id)
+            .fetch_optional(&self.pool)
+            .await?;
+            Ok(record)
+        })
+    }
+
+    fn latest_records(&self) -> StorageFuture<'_, Vec<PowerRecord>> {
+        Box::pin(async move {
+            let records = sqlx::query_as::<_, PowerRecord>(
+                r#"
+                SELECT * FROM power_records
+                WHERE id IN (SELECT MAX(id) FROM power_records GROUP BY room_id)
+                ORDER BY room_id
+                "#,
+            )
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(records)
+        })
+    }
+
+    fn history<'a>(
+        &'a self,
+        room_id: Option<&'a str>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<PowerRecord>> {
+        Box::pin(async move {
+            // `?` placeholders are positional, so every filter value is bound twice
+            let records = sqlx::query_as::<_, PowerRecord>(
+                r#"
+                SELECT * FROM (
+                    SELECT * FROM power_records
+                    WHERE (? IS NULL OR room_id = ?)
+                      AND (? IS NULL OR created_at >= ?)
+                      AND (? IS NULL OR created_at <= ?)
+                    ORDER BY created_at DESC, id DESC
+                    LIMIT ?
+                ) AS recent
+                ORDER BY created_at ASC, id ASC
+                "#,
+            )
+            .bind(room_id)
+            .bind(room_id)
+            .bind(from)
+            .bind(from)
+            .bind(to)
+            .bind(to)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(records)
+        })
+    }
+
+    fn rooms(&self) -> StorageFuture<'_, Vec<RoomSummary>> {
+        Box::pin(async move {
+            let rooms = sqlx::query_as::<_, RoomSummary>(
+                r#"
+                SELECT
+                    room_id,
+                    MAX(room_display_name) AS room_display_name,
+                    MAX(building_id) AS building_id,
+                    MAX(campus_id) AS campus_id,
+                    MAX(room_number) AS room_number,
+                    COUNT(*) AS record_count,
+                    MIN(created_at) AS first_seen,
+                    MAX(created_at) AS last_seen
+                FROM power_records
+                GROUP BY room_id
+                ORDER BY room_id
+                "#,
+            )
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(rooms)
+        })
+    }
+
+    fn recharges<'a>(
+        &'a self,
+        room_id: Option<&'a str>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<RechargeRecord>> {
+        Box::pin(async move {
+            let records = sqlx::query_as::<_, RechargeRecord>(
+                r#"
+                SELECT * FROM (
+                    SELECT * FROM recharges
+                    WHERE (? IS NULL OR room_id = ?)
+                      AND (? IS NULL OR created_at >= ?)
+                      AND (? IS NULL OR created_at <= ?)
+                    ORDER BY created_at DESC, id DESC
+                    LIMIT ?
+                ) AS recent
+                ORDER BY created_at ASC, id ASC
+                "#,
+            )
+            .bind(room_id)
+            .bind(room_id)
+            .bind(from)
+            .bind(from)
+            .bind(to)
+            .bind(to)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(records)
+        })
+    }
+
+    fn save_notification<'a>(
+        &'a self,
+        attempt: &'a NotificationAttempt<'a>,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            sqlx::query(
+                r#"
+                INSERT INTO notifications (
+                    account, event, channel, channel_type, success, error_class, retries,
+                    created_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(attempt.account)
+            .bind(attempt.event)
+            .bind(attempt.channel)
+            .bind(attempt.channel_type)
+            .bind(attempt.error_class.is_none())
+            .bind(attempt.error_class)
+            .bind(attempt.retries)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn notifications<'a>(
+        &'a self,
+        account: Option<&'a str>,
+        event: Option<&'a str>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<NotificationRecord>> {
+        Box::pin(async move {
+            let records = sqlx::query_as::<_, NotificationRecord>(
+                r#"
+                SELECT * FROM (
+                    SELECT * FROM notifications
+                    WHERE (? IS NULL OR account = ?)
+                      AND (? IS NULL OR event = ?)
+                      AND (? IS NULL OR created_at >= ?)
+                      AND (? IS NULL OR created_at <= ?)
+                    ORDER BY created_at DESC, id DESC
+                    LIMIT ?
+                ) AS recent
+                ORDER BY created_at ASC, id ASC
+                "#,
+            )
+            .bind(account)
+            .bind(account)
+            .bind(event)
+            .bind(event)
+            .bind(from)
+            .bind(from)
+            .bind(to)
+            .bind(to)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(records)
+        })
+    }
+
+    fn enqueue_outbox<'a>(&'a self, entry: &'a NewOutboxEntry<'a>) -> StorageFuture<'a, i64> {
+        Box::pin(async move {
+            let result = sqlx::query(
+                r#"
+                INSERT INTO notification_outbox (
+                    account, channel, event, payload, attempts, error_class,
+                    next_attempt_at, expires_at, created_at
+                )
+                VALUES (?, ?, ?, ?, 0, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(entry.account)
+            .bind(entry.channel)
+            .bind(entry.event)
+            .bind(entry.payload)
+            .bind(entry.error_class)
+            .bind(entry.next_attempt_at)
+            .bind(entry.expires_at)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+            Ok(result.last_insert_id() as i64)
+        })
+    }
+
+    fn due_outbox<'a>(
+        &'a self,
+        account: &'a str,
+        now: DateTime<Utc>,
+    ) -> StorageFuture<'a, Vec<OutboxEntry>> {
+        Box::pin(async move {
+            let entries = sqlx::query_as::<_, OutboxEntry>(
+                r#"
+                SELECT * FROM notification_outbox
+                WHERE account = ? AND next_attempt_at <= ?
+                ORDER BY id ASC
+                "#,
+            )
+            .bind(account)
+            .bind(now)
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(entries)
+        })
+    }
+
+    fn reschedule_outbox<'a>(
+        &'a self,
+        id: i64,
+        attempts: i64,
+        error_class: &'a str,
+        next_attempt_at: DateTime<Utc>,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            sqlx::query(
+                r#"
+                UPDATE notification_outbox
+                SET attempts = ?, error_class = ?, next_attempt_at = ?
+                WHERE id = ?
+                "#,
+            )
+            .bind(attempts)
+            .bind(error_class)
+            .bind(next_attempt_at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn delete_outbox(&self, id: i64) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            sqlx::query("DELETE FROM notification_outbox WHERE id = ?")
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn notification_state<'a>(
+        &'a self,
+        account: &'a str,
+    ) -> StorageFuture<'a, Option<NotificationState>> {
+        Box::pin(async move {
+            let state = sqlx::query_as::<_, NotificationState>(
+                r#"
+                SELECT * FROM notification_state
+                WHERE account = ?
+                "#,
+            )
+            .bind(account)
+            .fetch_optional(&self.pool)
+            .await?;
+            Ok(state)
+        })
+    }
+
+    fn save_notification_state<'a>(
+        &'a self,
+        state: &'a NotificationState,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            sqlx::query(
+                r#"
+                INSERT INTO notification_state (
+                    account, low_balance_threshold, last_low_balance_notify_at,
+                    last_heartbeat_date, consecutive_fetch_failures,
+                    last_fetch_failure_notify_at, last_depletion_notify_at, updated_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                ON DUPLICATE KEY UPDATE
+                    low_balance_threshold = VALUES(low_balance_threshold),
+                    last_low_balance_notify_at = VALUES(last_low_balance_notify_at),
+                    last_heartbeat_date = VALUES(last_heartbeat_date),
+                    consecutive_fetch_failures = VALUES(consecutive_fetch_failures),
+                    last_fetch_failure_notify_at = VALUES(last_fetch_failure_notify_at),
+                    last_depletion_notify_at = VALUES(last_depletion_notify_at),
+                    updated_at = VALUES(updated_at)
+                "#,
+            )
+            .bind(&state.account)
+            .bind(state.low_balance_threshold)
+            .bind(state.last_low_balance_notify_at)
+            .bind(state.last_heartbeat_date)
+            .bind(state.consecutive_fetch_failures)
+            .bind(state.last_fetch_failure_notify_at)
+            .bind(state.last_depletion_notify_at)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        })
+    }
+}
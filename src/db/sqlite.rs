@@ -0,0 +1,602 @@
+use super::{
+    Migration, NewOutboxEntry, NotificationAttempt, NotificationRecord, NotificationState,
+    OutboxEntry, PowerRecord, RechargeRecord, RoomSummary, Storage, StorageFuture,
+};
+use crate::api::PowerInfo;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::{Executor, Pool, Sqlite};
+use std::path::Path;
+use tracing::{debug, info};
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create power_records",
+        sql: include_str!("../../migrations/sqlite/0001_create_power_records.sql"),
+    },
+    Migration {
+        version: 2,
+        description: "create recharges",
+        sql: include_str!("../../migrations/sqlite/0002_create_recharges.sql"),
+    },
+    Migration {
+        version: 3,
+        description: "index records by room and time",
+        sql: include_str!("../../migrations/sqlite/0003_index_room_created_at.sql"),
+    },
+    Migration {
+        version: 4,
+        description: "store timestamps as RFC 3339 UTC",
+        sql: include_str!("../../migrations/sqlite/0004_utc_timestamps.sql"),
+    },
+    Migration {
+        version: 5,
+        description: "create notification_state",
+        sql: include_str!("../../migrations/sqlite/0005_create_notification_state.sql"),
+    },
+    Migration {
+        version: 6,
+        description: "create notifications",
+        sql: include_str!("../../migrations/sqlite/0006_create_notifications.sql"),
+    },
+    Migration {
+        version: 7,
+        description: "create notification_outbox",
+        sql: include_str!("../../migrations/sqlite/0007_create_notification_outbox.sql"),
+    },
+];
+
+pub struct SqliteStorage {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteStorage {
+    pub async fn connect(database_url: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        // Extract file path from database URL and ensure parent directory exists
+        if let Some(path) = database_url.strip_prefix("sqlite://") {
+            let db_path = Path::new(path);
+            if let Some(parent) = db_path.parent() {
+                if !parent.as_os_str().is_empty() {
+                    std::fs::create_dir_all(parent)?;
+                    debug!("Ensured database directory exists: {:?}", parent);
+                }
+            }
+        }
+
+        // Add create_if_missing option to connection string
+        let connection_url = if database_url.contains('?') {
+            format!("{}&mode=rwc", database_url)
+        } else {
+            format!("{}?mode=rwc", database_url)
+        };
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect(&connection_url)
+            .await?;
+
+        Ok(Self { pool })
+    }
+
+    /// Applies every migration newer than the recorded schema version.
+    async fn migrate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                description TEXT NOT NULL,
+                applied_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let current: i64 =
+            sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_version")
+                .fetch_one(&self.pool)
+                .await?;
+        debug!("Current schema version: {}", current);
+
+        for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+            info!(
+                "Applying migration {}: {}",
+                migration.version, migration.description
+            );
+            let mut tx = self.pool.begin().await?;
+            // Called through `Executor`, `RawSql::execute(&mut *tx)` fails the
+            // `Send` bound of `StorageFuture` with a higher-ranked lifetime error
+            (&mut *tx).execute(sqlx::raw_sql(migration.sql)).await?;
+            sqlx::query(
+                "INSERT INTO schema_version (version, description, applied_at) VALUES ($1, $2, $3)",
+            )
+            .bind(migration.version)
+            .bind(migration.description)
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn init(&self) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            info!("Initializing DB (SQLite)...");
+
+            // Enable WAL mode for better performance
+            sqlx::query("PRAGMA journal_mode=WAL")
+                .execute(&self.pool)
+                .await?;
+            sqlx::query("PRAGMA synchronous=NORMAL")
+                .execute(&self.pool)
+                .await?;
+
+            self.migrate().await?;
+
+            debug!("Database initialization completed");
+            Ok(())
+        })
+    }
+
+    fn save_data<'a>(&'a self, data: &'a PowerInfo) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            sqlx::query(
+                r#"
+                INSERT INTO power_records (
+                    remaining_energy, remaining_money, meter_room_id,
+                    room_display_name, room_id, building_id, campus_id, room_number,
+                    created_at
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+            )
+            .bind(data.remaining_energy)
+            .bind(data.remaining_money)
+            .bind(&data.meter_room_id)
+            .bind(&data.room_display_name)
+            .bind(&data.room_id)
+            .bind(&data.building_id)
+            .bind(&data.campus_id)
+            .bind(&data.room_number)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn save_recharge<'a>(
+        &'a self,
+        data: &'a PowerInfo,
+        amount: f64,
+        previous_money: f64,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            sqlx::query(
+                r#"
+                INSERT INTO recharges (
+                    room_id, room_display_name, amount, previous_money, current_money,
+                    created_at
+                )
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#,
+            )
+            .bind(&data.room_id)
+            .bind(&data.room_display_name)
+            .bind(amount)
+            .bind(previous_money)
+            .bind(data.remaining_money)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn latest_record<'a>(&'a self, room_id: &'a str) -> StorageFuture<'a, Option<PowerRecord>> {
+        Box::pin(async move {
+            let record = sqlx::query_as::<_, PowerRecord>(
+                r#"
+                SELECT * FROM power_records
+                WHERE room_id = $1
+                ORDER BY id DESC
+                LIMIT 1
+                "#,
+            )
+            .bind(room_id)
+            .fetch_optional(&self.pool)
+            .await?;
+            Ok(record)
+        })
+    }
+
+    fn latest_records(&self) -> StorageFuture<'_, Vec<PowerRecord>> {
+        Box::pin(async move {
+            let records = sqlx::query_as::<_, PowerRecord>(
+                r#"
+                SELECT * FROM power_records
+                WHERE id IN (SELECT MAX(id) FROM power_records GROUP BY room_id)
+                ORDER BY room_id
+                "#,
+            )
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(records)
+        })
+    }
+
+    fn history<'a>(
+        &'a self,
+        room_id: Option<&'a str>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<PowerRecord>> {
+        Box::pin(async move {
+            let records = sqlx::query_as::<_, PowerRecord>(
+                r#"
+                SELECT * FROM (
+                    SELECT * FROM power_records
+                    WHERE ($1 IS NULL OR room_id = $1)
+                      AND ($2 IS NULL OR created_at >= $2)
+                      AND ($3 IS NULL OR created_at <= $3)
+                    ORDER BY created_at DESC, id DESC
+                    LIMIT $4
+                )
+                ORDER BY created_at ASC, id ASC
+                "#,
+            )
+            .bind(room_id)
+            .bind(from)
+            .bind(to)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(records)
+        })
+    }
+
+    fn rooms(&self) -> StorageFuture<'_, Vec<RoomSummary>> {
+        Box::pin(async move {
+            let rooms = sqlx::query_as::<_, RoomSummary>(
+                r#"
+                SELECT
+                    room_id,
+                    room_display_name,
+                    building_id,
+                    campus_id,
+                    room_number,
+                    COUNT(*) AS record_count,
+                    MIN(created_at) AS first_seen,
+                    MAX(created_at) AS last_seen
+                FROM power_records
+                GROUP BY room_id
+                ORDER BY room_id
+                "#,
+            )
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(rooms)
+        })
+    }
+
+    fn recharges<'a>(
+        &'a self,
+        room_id: Option<&'a str>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<RechargeRecord>> {
+        Box::pin(async move {
+            let records = sqlx::query_as::<_, RechargeRecord>(
+                r#"
+                SELECT * FROM (
+                    SELECT * FROM recharges
+                    WHERE ($1 IS NULL OR room_id = $1)
+                      AND ($2 IS NULL OR created_at >= $2)
+                      AND ($3 IS NULL OR created_at <= $3)
+                    ORDER BY created_at DESC, id DESC
+                    LIMIT $4
+                )
+                ORDER BY created_at ASC, id ASC
+                "#,
+            )
+            .bind(room_id)
+            .bind(from)
+            .bind(to)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(records)
+        })
+    }
+
+    fn save_notification<'a>(
+        &'a self,
+        attempt: &'a NotificationAttempt<'a>,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            sqlx::query(
+                r#"
+                INSERT INTO notifications (
+                    account, event, channel, channel_type, success, error_class, retries,
+                    created_at
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                "#,
+            )
+            .bind(attempt.account)
+            .bind(attempt.event)
+            .bind(attempt.channel)
+            .bind(attempt.channel_type)
+            .bind(attempt.error_class.is_none())
+            .bind(attempt.error_class)
+            .bind(attempt.retries)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn notifications<'a>(
+        &'a self,
+        account: Option<&'a str>,
+        event: Option<&'a str>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<NotificationRecord>> {
+        Box::pin(async move {
+            let records = sqlx::query_as::<_, NotificationRecord>(
+                r#"
+                SELECT * FROM (
+                    SELECT * FROM notifications
+                    WHERE ($1 IS NULL OR account = $1)
+                      AND ($2 IS NULL OR event = $2)
+                      AND ($3 IS NULL OR created_at >= $3)
+                      AND ($4 IS NULL OR created_at <= $4)
+                    ORDER BY created_at DESC, id DESC
+                    LIMIT $5
+                )
+                ORDER BY created_at ASC, id ASC
+                "#,
+            )
+            .bind(account)
+            .bind(event)
+            .bind(from)
+            .bind(to)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(records)
+        })
+    }
+
+    fn enqueue_outbox<'a>(&'a self, entry: &'a NewOutboxEntry<'a>) -> StorageFuture<'a, i64> {
+        Box::pin(async move {
+            let result = sqlx::query(
+                r#"
+                INSERT INTO notification_outbox (
+                    account, channel, event, payload, attempts, error_class,
+                    next_attempt_at, expires_at, created_at
+                )
+                VALUES ($1, $2, $3, $4, 0, $5, $6, $7, $8)
+                "#,
+            )
+            .bind(entry.account)
+            .bind(entry.channel)
+            .bind(entry.event)
+            .bind(entry.payload)
+            .bind(entry.error_class)
+            .bind(entry.next_attempt_at)
+            .bind(entry.expires_at)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+            Ok(result.last_insert_rowid())
+        })
+    }
+
+    fn due_outbox<'a>(
+        &'a self,
+        account: &'a str,
+        now: DateTime<Utc>,
+    ) -> StorageFuture<'a, Vec<OutboxEntry>> {
+        Box::pin(async move {
+            let entries = sqlx::query_as::<_, OutboxEntry>(
+                r#"
+                SELECT * FROM notification_outbox
+                WHERE account = $1 AND next_attempt_at <= $2
+                ORDER BY id ASC
+                "#,
+            )
+            .bind(account)
+            .bind(now)
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(entries)
+        })
+    }
+
+    fn reschedule_outbox<'a>(
+        &'a self,
+        id: i64,
+        attempts: i64,
+        error_class: &'a str,
+        next_attempt_at: DateTime<Utc>,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            sqlx::query(
+                r#"
+                UPDATE notification_outbox
+                SET attempts = $1, error_class = $2, next_attempt_at = $3
+                WHERE id = $4
+                "#,
+            )
+            .bind(attempts)
+            .bind(error_class)
+            .bind(next_attempt_at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn delete_outbox(&self, id: i64) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            sqlx::query("DELETE FROM notification_outbox WHERE id = $1")
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn notification_state<'a>(
+        &'a self,
+        account: &'a str,
+    ) -> StorageFuture<'a, Option<NotificationState>> {
+        Box::pin(async move {
+            let state = sqlx::query_as::<_, NotificationState>(
+                r#"
+                SELECT * FROM notification_state
+                WHERE account = $1
+                "#,
+            )
+            .bind(account)
+            .fetch_optional(&self.pool)
+            .await?;
+            Ok(state)
+        })
+    }
+
+    fn save_notification_state<'a>(
+        &'a self,
+        state: &'a NotificationState,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            sqlx::query(
+                r#"
+                INSERT INTO notification_state (
+                    account, low_balance_threshold, last_low_balance_notify_at,
+                    last_heartbeat_date, consecutive_fetch_failures,
+                    last_fetch_failure_notify_at, last_depletion_notify_at, updated_at
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (account) DO UPDATE SET
+                    low_balance_threshold = excluded.low_balance_threshold,
+                    last_low_balance_notify_at = excluded.last_low_balance_notify_at,
+                    last_heartbeat_date = excluded.last_heartbeat_date,
+                    consecutive_fetch_failures = excluded.consecutive_fetch_failures,
+                    last_fetch_failure_notify_at = excluded.last_fetch_failure_notify_at,
+                    last_depletion_notify_at = excluded.last_depletion_notify_at,
+                    updated_at = excluded.updated_at
+                "#,
+            )
+            .bind(&state.account)
+            .bind(state.low_balance_threshold)
+            .bind(state.last_low_balance_notify_at)
+            .bind(state.last_heartbeat_date)
+            .bind(state.consecutive_fetch_failures)
+            .bind(state.last_fetch_failure_notify_at)
+            .bind(state.last_depletion_notify_at)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn migrations_upgrade_a_legacy_database() {
+        let storage = SqliteStorage::connect("sqlite::memory:").await.unwrap();
+        // A database from before schema_version, with CURRENT_TIMESTAMP rows
+        (&storage.pool)
+            .execute(sqlx::raw_sql(concat!(
+                include_str!("../../migrations/sqlite/0001_create_power_records.sql"),
+                include_str!("../../migrations/sqlite/0002_create_recharges.sql"),
+            )))
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO power_records (
+                remaining_energy, remaining_money, meter_room_id, room_display_name,
+                room_id, building_id, campus_id, room_number, created_at
+            )
+            VALUES (26.9, 4.4, 'm1', 'Room 101', 'r1', 'b1', 'a1', '101', '2025-01-01 08:00:00');
+            INSERT INTO recharges (
+                room_id, room_display_name, amount, previous_money, current_money, created_at
+            )
+            VALUES ('r1', 'Room 101', 50.0, 4.4, 54.4, '2025-01-01 09:30:00');
+            "#,
+        )
+        .execute(&storage.pool)
+        .await
+        .unwrap();
+
+        storage.init().await.unwrap();
+        // Nothing is applied twice
+        storage.init().await.unwrap();
+        let version: i64 = sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
+            .fetch_one(&storage.pool)
+            .await
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.last().unwrap().version);
+
+        let at = |hour, minute| Utc.with_ymd_and_hms(2025, 1, 1, hour, minute, 0).unwrap();
+        let history = storage
+            .history(Some("r1"), Some(at(7, 0)), Some(at(9, 0)), 10)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].remaining_money, 4.4);
+        assert_eq!(history[0].created_at, at(8, 0));
+
+        let recharges = storage
+            .recharges(Some("r1"), Some(at(9, 0)), None, 10)
+            .await
+            .unwrap();
+        assert_eq!(recharges.len(), 1);
+        assert_eq!(recharges[0].amount, 50.0);
+        assert_eq!(recharges[0].created_at, at(9, 30));
+
+        let id = storage
+            .enqueue_outbox(&NewOutboxEntry {
+                account: "alice",
+                channel: "console",
+                event: "low_balance",
+                payload: "{}",
+                error_class: Some("timeout"),
+                next_attempt_at: at(10, 0),
+                expires_at: at(11, 0),
+            })
+            .await
+            .unwrap();
+        assert!(
+            storage
+                .due_outbox("alice", at(9, 59))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let due = storage.due_outbox("alice", at(10, 0)).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].id, id);
+        assert_eq!(due[0].error_class.as_deref(), Some("timeout"));
+        assert_eq!(due[0].expires_at, at(11, 0));
+    }
+}
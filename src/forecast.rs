@@ -0,0 +1,131 @@
+use crate::db::PowerRecord;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Minimum time span the history must cover before a rate is estimated.
+const MIN_SPAN_HOURS: i64 = 6;
+
+/// Consumption rate derived from the recent history of a room.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsumptionForecast {
+    /// Average energy used per day (kWh/day)
+    pub kwh_per_day: f64,
+    /// Average money spent per day (CNY/day)
+    pub cny_per_day: f64,
+    /// Days until the balance reaches zero at the current rate
+    pub days_until_empty: Option<f64>,
+    /// Projected time the balance reaches zero
+    pub depletion_time: Option<DateTime<Local>>,
+    /// Length of the history the rate was computed from, in days
+    pub window_days: f64,
+}
+
+/// Estimates the consumption rate from records ordered from oldest to newest.
+///
+/// Only decreases between consecutive samples count as consumption, so
+/// recharges (balance jumps up) do not distort the rate. Returns `None` when
+/// the history is too short to give a meaningful estimate.
+pub fn estimate(records: &[PowerRecord], current_money: f64) -> Option<ConsumptionForecast> {
+    let first = records.first()?;
+    let last = records.last()?;
+    let span = last.created_at.signed_duration_since(first.created_at);
+    if span.num_hours() < MIN_SPAN_HOURS {
+        return None;
+    }
+
+    let mut money_used = 0.0;
+    let mut energy_used = 0.0;
+    for pair in records.windows(2) {
+        money_used += (pair[0].remaining_money - pair[1].remaining_money).max(0.0);
+        energy_used += (pair[0].remaining_energy - pair[1].remaining_energy).max(0.0);
+    }
+
+    let window_days = span.num_seconds() as f64 / 86_400.0;
+    let cny_per_day = money_used / window_days;
+    let kwh_per_day = energy_used / window_days;
+
+    let days_until_empty = if cny_per_day > 0.0 {
+        Some((current_money / cny_per_day).max(0.0))
+    } else {
+        None
+    };
+    let depletion_time = days_until_empty
+        .and_then(|days| chrono::Duration::try_seconds((days * 86_400.0) as i64))
+        .and_then(|remaining| Utc::now().checked_add_signed(remaining))
+        .map(|time| time.with_timezone(&Local));
+
+    Some(ConsumptionForecast {
+        kwh_per_day,
+        cny_per_day,
+        days_until_empty,
+        depletion_time,
+        window_days,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// A record taken `hours` after the start of the window.
+    fn record(hours: i64, money: f64, energy: f64) -> PowerRecord {
+        PowerRecord {
+            id: hours,
+            remaining_energy: energy,
+            remaining_money: money,
+            meter_room_id: "m1".to_string(),
+            room_display_name: "Room 101".to_string(),
+            room_id: "r1".to_string(),
+            building_id: "b1".to_string(),
+            campus_id: "a1".to_string(),
+            room_number: "101".to_string(),
+            created_at: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+                + chrono::Duration::hours(hours),
+        }
+    }
+
+    #[test]
+    fn estimate_needs_the_minimum_span() {
+        assert!(estimate(&[], 10.0).is_none());
+        assert!(estimate(&[record(0, 20.0, 40.0)], 20.0).is_none());
+        let records = [
+            record(0, 20.0, 40.0),
+            record(MIN_SPAN_HOURS - 1, 18.0, 36.0),
+        ];
+        assert!(estimate(&records, 18.0).is_none());
+        let records = [record(0, 20.0, 40.0), record(MIN_SPAN_HOURS, 18.0, 36.0)];
+        assert!(estimate(&records, 18.0).is_some());
+    }
+
+    #[test]
+    fn estimate_ignores_a_recharge_mid_window() {
+        let records = [
+            record(0, 10.0, 20.0),
+            record(12, 8.0, 16.0),
+            record(13, 58.0, 116.0),
+            record(24, 56.0, 112.0),
+        ];
+        let forecast = estimate(&records, 56.0).unwrap();
+        assert_eq!(forecast.window_days, 1.0);
+        assert_eq!(forecast.cny_per_day, 4.0);
+        assert_eq!(forecast.kwh_per_day, 8.0);
+        assert_eq!(forecast.days_until_empty, Some(14.0));
+        assert!(forecast.depletion_time.is_some());
+    }
+
+    #[test]
+    fn estimate_flat_balance_never_depletes() {
+        let records = [
+            record(0, 30.0, 60.0),
+            record(12, 30.0, 60.0),
+            record(48, 30.0, 60.0),
+        ];
+        let forecast = estimate(&records, 30.0).unwrap();
+        assert_eq!(forecast.window_days, 2.0);
+        assert_eq!(forecast.cny_per_day, 0.0);
+        assert_eq!(forecast.kwh_per_day, 0.0);
+        assert_eq!(forecast.days_until_empty, None);
+        assert_eq!(forecast.depletion_time, None);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::api::PowerInfo;
+use crate::config::InfluxConfig;
+use chrono::Utc;
+use reqwest::Client;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+/// Handle to the background task that batches samples and writes them to
+/// InfluxDB. Cloning is cheap; all clones feed the same buffer.
+#[derive(Clone)]
+pub struct InfluxSink {
+    sender: mpsc::UnboundedSender<String>,
+    measurement: String,
+}
+
+impl InfluxSink {
+    /// Starts the writer task. Returns `None` if the sink is disabled.
+    pub fn spawn(config: &InfluxConfig) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        if config.url.is_empty() {
+            return Err("influx.url is required when influx.enabled = true".into());
+        }
+        let url = reqwest::Url::parse(&config.url)?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err("influx.url must use http or https".into());
+        }
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let writer = InfluxWriter {
+            client: Client::builder().timeout(Duration::from_secs(10)).build()?,
+            url,
+            token: config.token.clone(),
+            batch_size: config.batch_size.max(1),
+            max_buffer: config.max_buffer.max(config.batch_size.max(1)),
+            buffer: VecDeque::new(),
+        };
+        let flush_interval = Duration::from_secs(config.flush_interval_seconds.max(1));
+        tokio::spawn(writer.run(receiver, flush_interval));
+        info!(
+            "InfluxDB sink enabled (batch size {}, flush every {:?})",
+            config.batch_size, flush_interval
+        );
+
+        Ok(Some(Self {
+            sender,
+            measurement: config.measurement.clone(),
+        }))
+    }
+
+    /// Queues a sample for writing. Never blocks the polling loop.
+    pub fn record(&self, info: &PowerInfo) {
+        let line = to_line_protocol(&self.measurement, info);
+        if self.sender.send(line).is_err() {
+            error!("InfluxDB writer task has stopped, sample dropped");
+        }
+    }
+}
+
+struct InfluxWriter {
+    client: Client,
+    url: reqwest::Url,
+    token: String,
+    batch_size: usize,
+    max_buffer: usize,
+    buffer: VecDeque<String>,
+}
+
+impl InfluxWriter {
+    async fn run(
+        mut self,
+        mut receiver: mpsc::UnboundedReceiver<String>,
+        flush_interval: Duration,
+    ) {
+        let mut ticker = tokio::time::interval(flush_interval);
+        ticker.tick().await;
+        loop {
+            tokio::select! {
+                line = receiver.recv() => match line {
+                    Some(line) => {
+                        self.push(line);
+                        if self.buffer.len() >= self.batch_size {
+                            self.flush().await;
+                        }
+                    }
+                    None => {
+                        self.flush().await;
+                        return;
+                    }
+                },
+                _ = ticker.tick() => self.flush().await,
+            }
+        }
+    }
+
+    fn push(&mut self, line: String) {
+        if self.buffer.len() >= self.max_buffer {
+            self.buffer.pop_front();
+            warn!(
+                "InfluxDB buffer full ({} samples), dropping the oldest sample",
+                self.max_buffer
+            );
+        }
+        self.buffer.push_back(line);
+    }
+
+    /// Writes buffered samples in batches, keeping them if a write fails so
+    /// they are retried on the next flush.
+    async fn flush(&mut self) {
+        while !self.buffer.is_empty() {
+            let count = self.buffer.len().min(self.batch_size);
+            let body = self
+                .buffer
+                .iter()
+                .take(count)
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join("\n");
+            match self.write(body).await {
+                Ok(()) => {
+                    self.buffer.drain(..count);
+                    debug!("Wrote {} sample(s) to InfluxDB", count);
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to write to InfluxDB, keeping {} buffered sample(s): {}",
+                        self.buffer.len(),
+                        e
+                    );
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn write(&self, body: String) -> Result<(), Box<dyn std::error::Error>> {
+        let mut request = self
+            .client
+            .post(self.url.clone())
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(body);
+        if !self.token.is_empty() {
+            request = request.header("Authorization", format!("Token {}", self.token));
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("InfluxDB returned {}: {}", status, text).into());
+        }
+        Ok(())
+    }
+}
+
+/// Formats a sample as one line of InfluxDB line protocol with a nanosecond
+/// timestamp, tagged with the room location.
+fn to_line_protocol(measurement: &str, info: &PowerInfo) -> String {
+    let mut line = escape(measurement, &[',', ' ']);
+    let tags = [
+        ("room_id", &info.room_id),
+        ("building_id", &info.building_id),
+        ("campus_id", &info.campus_id),
+        ("room_number", &info.room_number),
+        ("room_name", &info.room_display_name),
+    ];
+    for (key, value) in tags {
+        // line protocol does not allow empty tag values
+        if !value.is_empty() {
+            line.push_str(&format!(",{}={}", key, escape(value, &[',', '=', ' '])));
+        }
+    }
+    line.push_str(&format!(
+        " remaining_money={},remaining_energy={}",
+        info.remaining_money, info.remaining_energy
+    ));
+    if let Some(timestamp) = Utc::now().timestamp_nanos_opt() {
+        line.push_str(&format!(" {}", timestamp));
+    }
+    line
+}
+
+fn escape(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        // a newline would terminate the line
+        if c == '\n' || c == '\r' {
+            continue;
+        }
+        if special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(room_name: &str) -> PowerInfo {
+        serde_json::from_value(serde_json::json!({
+            "retcode": 0,
+            "msg": "",
+            "sydl": "26.9",
+            "syje": "4.4",
+            "dffjbh": "m1",
+            "roomName": room_name,
+            "roomId": "r1",
+            "buiId": "b1",
+            "areaid": "",
+            "fjh": "101",
+        }))
+        .unwrap()
+    }
+
+    fn writer(max_buffer: usize) -> InfluxWriter {
+        InfluxWriter {
+            client: Client::new(),
+            url: reqwest::Url::parse("http://127.0.0.1:1/api/v2/write").unwrap(),
+            token: String::new(),
+            batch_size: 2,
+            max_buffer,
+            buffer: VecDeque::new(),
+        }
+    }
+
+    #[test]
+    fn line_protocol_escapes_tag_values() {
+        let line = to_line_protocol("power usage,v2", &info("Room A, East=1\n"));
+        let (line, timestamp) = line.rsplit_once(' ').unwrap();
+        assert!(timestamp.parse::<i64>().is_ok());
+        // The empty campus_id tag is left out
+        assert_eq!(
+            line,
+            "power\\ usage\\,v2,room_id=r1,building_id=b1,room_number=101,\
+             room_name=Room\\ A\\,\\ East\\=1 remaining_money=4.4,remaining_energy=26.9"
+        );
+    }
+
+    #[test]
+    fn full_buffer_drops_the_oldest_sample() {
+        let mut writer = writer(3);
+        for i in 0..5 {
+            writer.push(format!("line{}", i));
+        }
+        assert_eq!(writer.buffer, ["line2", "line3", "line4"]);
+    }
+
+    #[tokio::test]
+    async fn failed_writes_keep_the_buffer() {
+        let mut writer = writer(3);
+        writer.push("line0".to_string());
+        writer.push("line1".to_string());
+        writer.flush().await;
+        assert_eq!(writer.buffer, ["line0", "line1"]);
+    }
+}
@@ -1,18 +1,26 @@
 pub mod api;
+pub mod cli;
 pub mod config;
 pub mod db;
+pub mod forecast;
+pub mod influx;
+pub mod metrics;
+pub mod monitor;
+pub mod mqtt;
 pub mod notify;
+pub mod server;
+pub mod template;
 pub mod utils;
 
-use crate::api::ApiService;
 use crate::config::AppConfig;
 use crate::db::DbService;
-use crate::notify::NotificationManager;
-use crate::utils::retry;
+use crate::influx::InfluxSink;
+use crate::monitor::{AccountMonitor, Sinks};
+use crate::mqtt::MqttPublisher;
+use futures::future::join_all;
 use std::time::Duration;
-use tokio::time::sleep;
 
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
     info!("Starting Uestc Power Monitor...");
@@ -27,35 +35,48 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
     // initialize services
-    debug!("Initializing API service...");
-    let api_service = match retry(|| ApiService::new(&config), 3, Duration::from_secs(5)).await {
-        Ok(service) => {
-            debug!("API service initialized");
-            service
-        }
-        Err(e) => {
-            error!("Failed to initialize API service (login failed): {}", e);
-            // Try to send login failure notification
-            if let Some(manager) = NotificationManager::new(config.notify.clone()) {
-                manager
-                    .notify_login_failure(&format!("Failed to login: {}", e))
-                    .await;
-            }
-            return Err(e);
-        }
-    };
-
     debug!("Initializing database service...");
     let db_service = DbService::new(config.database_url.clone()).await?;
     db_service.init().await?;
     debug!("Database service initialized");
 
-    debug!("Initializing notification manager...");
-    let mut notification_manager = NotificationManager::new(config.notify.clone());
-    debug!(
-        "Notification manager initialized: {:?}",
-        notification_manager.is_some()
-    );
+    let accounts = config.get_accounts();
+    debug!("Initializing {} account(s)...", accounts.len());
+    // concurrently, so an account retrying its login does not hold up the others
+    let results = join_all(accounts.iter().map(|account| async {
+        let notify_config = account.notify_config(&config.notify)?;
+        AccountMonitor::new(account, notify_config, &db_service).await
+    }))
+    .await;
+    let mut monitors = Vec::new();
+    let mut last_error = None;
+    for result in results {
+        match result {
+            Ok(monitor) => monitors.push(monitor),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    if monitors.is_empty() {
+        return Err(last_error.unwrap_or_else(|| "No accounts configured".into()));
+    }
+    if monitors.len() < accounts.len() {
+        warn!(
+            "{} of {} account(s) failed to initialize and will not be monitored",
+            accounts.len() - monitors.len(),
+            accounts.len()
+        );
+    }
+
+    let sinks = Sinks {
+        influx: InfluxSink::spawn(&config.influx)?,
+        mqtt: MqttPublisher::spawn(&config.mqtt)?,
+    };
+
+    if config.server.enabled {
+        debug!("Starting HTTP server...");
+        server::spawn(&config.server, db_service.clone()).await?;
+    }
 
     let interval = Duration::from_secs(config.interval_seconds);
     debug!(
@@ -63,72 +84,33 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
         config.interval_seconds
     );
 
-    // main loop
-    loop {
-        tokio::select! {
-            _ = tokio::signal::ctrl_c() => {
-                info!("Received SIGINT, shutting down gracefully...");
-                break;
+    // main loop: every account is polled concurrently on its own schedule
+    let monitoring = join_all(
+        monitors
+            .iter_mut()
+            .map(|monitor| monitor.run(&db_service, &sinks, interval)),
+    );
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {
+            info!("Received SIGINT, shutting down gracefully...");
+        }
+        _ = async {
+            #[cfg(unix)]
+            {
+                let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                    .expect("Failed to setup SIGTERM handler");
+                sigterm.recv().await
             }
-            _ = async {
-                #[cfg(unix)]
-                {
-                    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
-                        .expect("Failed to setup SIGTERM handler");
-                    sigterm.recv().await
-                }
-                #[cfg(not(unix))]
-                {
-                    std::future::pending::<()>().await;
-                    Some(())
-                }
-            } => {
-                info!("Received SIGTERM, shutting down gracefully...");
-                break;
+            #[cfg(not(unix))]
+            {
+                std::future::pending::<()>().await;
+                Some(())
             }
-            _ = async {
-                debug!("Fetching power data...");
-                match retry(|| api_service.fetch_data(), 3, Duration::from_secs(2)).await {
-                    Ok(Some(data)) => {
-                        debug!("Data fetched successfully: room={}, money={:.2}, energy={:.2}",
-                            data.room_display_name, data.remaining_money, data.remaining_energy);
-
-                        // Reset consecutive failure counter on success
-                        if let Some(manager) = &mut notification_manager {
-                            manager.reset_fetch_failures();
-                        }
-
-                        // save data to database
-                        if let Err(e) = db_service.save_data(&data).await {
-                            error!("Failed to save data: {}", e);
-                        }
-
-                        // notify logic
-                        if let Some(manager) = &mut notification_manager {
-                            debug!("Checking notification conditions...");
-                            manager.check_and_notify(&data).await;
-                        }
-                    }
-                    Ok(None) => {
-                        debug!("No data returned from API (details logged above)");
-                        // Record as a fetch failure
-                        if let Some(manager) = &mut notification_manager {
-                            manager.record_fetch_failure().await;
-                        }
-                    }
-                    Err(e) => {
-                        error!("Failed to fetch data: {}", e);
-                        // Record consecutive fetch failure
-                        if let Some(manager) = &mut notification_manager {
-                            manager.record_fetch_failure().await;
-                        }
-                    }
-                }
-
-                debug!("Sleeping for {:?}...", interval);
-                sleep(interval).await;
-            } => {}
+        } => {
+            info!("Received SIGTERM, shutting down gracefully...");
         }
+        _ = monitoring => {}
     }
 
     info!("Shutdown complete");
@@ -1,6 +1,8 @@
+use clap::Parser;
 use tracing::error;
 use tracing_subscriber::fmt::format::Writer;
 use tracing_subscriber::fmt::time::FormatTime;
+use uestc_power_monitor::cli::{Cli, Command};
 
 // Custom time formatter that uses local timezone (respects TZ environment variable)
 struct LocalTimeFormatter;
@@ -17,16 +19,24 @@ impl FormatTime for LocalTimeFormatter {
 
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Run);
+
     // Initialize logging with default filter (info) if RUST_LOG is not set
     let filter = tracing_subscriber::EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
 
-    tracing_subscriber::fmt()
+    // Keep stdout clean for commands that print results (e.g. `export`)
+    let subscriber = tracing_subscriber::fmt()
         .with_env_filter(filter)
-        .with_timer(LocalTimeFormatter)
-        .init();
+        .with_timer(LocalTimeFormatter);
+    if command.writes_stdout() {
+        subscriber.with_writer(std::io::stderr).init();
+    } else {
+        subscriber.init();
+    }
 
-    if let Err(e) = uestc_power_monitor::run().await {
+    if let Err(e) = uestc_power_monitor::cli::execute(command).await {
         error!("Error: {}", e);
         std::process::exit(1);
     }
@@ -0,0 +1,161 @@
+use crate::api::PowerInfo;
+use prometheus::{CounterVec, Encoder, GaugeVec, Opts, Registry, TextEncoder};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use tracing::error;
+
+/// Process-wide Prometheus metrics, served on `/metrics` by the HTTP server.
+pub struct Metrics {
+    registry: Registry,
+    remaining_money: GaugeVec,
+    remaining_energy: GaugeVec,
+    room_info: GaugeVec,
+    /// Room name currently exported on `room_info`, by room id
+    room_names: Mutex<HashMap<String, String>>,
+    last_fetch_success: GaugeVec,
+    consecutive_fetch_failures: GaugeVec,
+    fetch_failures: CounterVec,
+    login_attempts: CounterVec,
+    notifier_send_failures: CounterVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+fn gauge_vec(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> GaugeVec {
+    let gauge = GaugeVec::new(Opts::new(name, help), labels).expect("valid gauge definition");
+    registry
+        .register(Box::new(gauge.clone()))
+        .expect("gauge registered once");
+    gauge
+}
+
+fn counter_vec(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> CounterVec {
+    let counter = CounterVec::new(Opts::new(name, help), labels).expect("valid counter definition");
+    registry
+        .register(Box::new(counter.clone()))
+        .expect("counter registered once");
+    counter
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        Self {
+            remaining_money: gauge_vec(
+                &registry,
+                "upm_remaining_money_cny",
+                "Remaining balance of the room in CNY",
+                &["room_id"],
+            ),
+            remaining_energy: gauge_vec(
+                &registry,
+                "upm_remaining_energy_kwh",
+                "Remaining energy of the room in kWh",
+                &["room_id"],
+            ),
+            room_info: gauge_vec(
+                &registry,
+                "upm_room_info",
+                "Room metadata, always 1",
+                &["room_id", "room_name"],
+            ),
+            room_names: Mutex::new(HashMap::new()),
+            last_fetch_success: gauge_vec(
+                &registry,
+                "upm_last_fetch_success_timestamp_seconds",
+                "Unix timestamp of the last successful fetch",
+                &["account"],
+            ),
+            consecutive_fetch_failures: gauge_vec(
+                &registry,
+                "upm_consecutive_fetch_failures",
+                "Number of consecutive failed fetches",
+                &["account"],
+            ),
+            fetch_failures: counter_vec(
+                &registry,
+                "upm_fetch_failures_total",
+                "Total number of failed fetches",
+                &["account"],
+            ),
+            login_attempts: counter_vec(
+                &registry,
+                "upm_login_attempts_total",
+                "Total number of login attempts by result",
+                &["account", "result"],
+            ),
+            notifier_send_failures: counter_vec(
+                &registry,
+                "upm_notifier_send_failures_total",
+                "Total number of notifications that failed after all retries",
+                &["notify_type"],
+            ),
+            registry,
+        }
+    }
+
+    pub fn record_fetch_success(&self, account: &str, info: &PowerInfo) {
+        let room_id = info.room_id.as_str();
+        self.remaining_money
+            .with_label_values(&[room_id])
+            .set(info.remaining_money);
+        self.remaining_energy
+            .with_label_values(&[room_id])
+            .set(info.remaining_energy);
+        self.record_room_name(room_id, &info.room_display_name);
+        self.last_fetch_success
+            .with_label_values(&[account])
+            .set(chrono::Utc::now().timestamp() as f64);
+    }
+
+    /// Exports the room name on `room_info`, dropping the series of a
+    /// previous name so a renamed room keeps a single series.
+    fn record_room_name(&self, room_id: &str, room_name: &str) {
+        let mut room_names = self.room_names.lock().unwrap();
+        let previous = room_names.insert(room_id.to_string(), room_name.to_string());
+        if let Some(previous) = previous.filter(|previous| previous != room_name) {
+            let _ = self.room_info.remove_label_values(&[room_id, &previous]);
+        }
+        self.room_info
+            .with_label_values(&[room_id, room_name])
+            .set(1.0);
+    }
+
+    pub fn record_fetch_failure(&self, account: &str) {
+        self.fetch_failures.with_label_values(&[account]).inc();
+    }
+
+    /// Exports the consecutive failure count kept (and persisted) by the
+    /// notification manager of the account.
+    pub fn set_consecutive_fetch_failures(&self, account: &str, count: u32) {
+        self.consecutive_fetch_failures
+            .with_label_values(&[account])
+            .set(f64::from(count));
+    }
+
+    pub fn record_login_attempt(&self, account: &str, success: bool) {
+        let result = if success { "success" } else { "failure" };
+        self.login_attempts
+            .with_label_values(&[account, result])
+            .inc();
+    }
+
+    pub fn record_notifier_failure(&self, notify_type: &str) {
+        self.notifier_send_failures
+            .with_label_values(&[notify_type])
+            .inc();
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn gather(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            error!("Failed to encode metrics: {}", e);
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
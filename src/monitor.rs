@@ -0,0 +1,226 @@
+use crate::api::{ApiService, PowerInfo};
+use crate::config::{AccountConfig, NotifyConfig};
+use crate::db::DbService;
+use crate::forecast::{self, ConsumptionForecast};
+use crate::influx::InfluxSink;
+use crate::metrics::metrics;
+use crate::mqtt::MqttPublisher;
+use crate::notify::{NotificationManager, PowerReport, StateStore};
+use crate::utils::retry;
+use chrono::Utc;
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{debug, error, info, warn};
+
+/// Balance increases below this amount are floating point noise, not recharges
+/// (the API reports balances with two decimal places).
+const MIN_RECHARGE_AMOUNT: f64 = 0.005;
+
+/// Optional destinations that receive every fetched sample besides the database.
+#[derive(Clone, Default)]
+pub struct Sinks {
+    pub influx: Option<InfluxSink>,
+    pub mqtt: Option<MqttPublisher>,
+}
+
+/// Polls a single account and drives its notifications.
+pub struct AccountMonitor {
+    name: String,
+    api_service: ApiService,
+    notification_manager: Option<NotificationManager>,
+}
+
+impl AccountMonitor {
+    pub async fn new(
+        account: &AccountConfig,
+        notify_config: NotifyConfig,
+        db_service: &DbService,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let store = StateStore {
+            db: db_service.clone(),
+            account: account.name.clone(),
+        };
+        if account.threshold.is_some() && !notify_config.threshold_tiers.is_empty() {
+            warn!(
+                "[{}] threshold is ignored, notify.threshold_tiers is set",
+                account.name
+            );
+        }
+
+        debug!("[{}] Initializing API service...", account.name);
+        let api_service = match retry(|| ApiService::new(account), 3, Duration::from_secs(5)).await
+        {
+            Ok(service) => {
+                debug!("[{}] API service initialized", account.name);
+                service
+            }
+            Err(e) => {
+                error!(
+                    "[{}] Failed to initialize API service (login failed): {}",
+                    account.name, e
+                );
+                // Try to send login failure notification. Without a store the
+                // one-shot manager starts no outbox worker for a dropped account
+                if let Some(manager) = NotificationManager::new(notify_config, None).await {
+                    manager
+                        .notify_login_failure(&account.name, &e.to_string())
+                        .await;
+                }
+                return Err(e);
+            }
+        };
+
+        debug!("[{}] Initializing notification manager...", account.name);
+        let notification_manager = NotificationManager::new(notify_config, Some(store)).await;
+        debug!(
+            "[{}] Notification manager initialized: {:?}",
+            account.name,
+            notification_manager.is_some()
+        );
+
+        Ok(Self {
+            name: account.name.clone(),
+            api_service,
+            notification_manager,
+        })
+    }
+
+    /// Runs the fetch/save/notify cycle for this account forever.
+    pub async fn run(&mut self, db_service: &DbService, sinks: &Sinks, interval: Duration) {
+        info!("[{}] Monitoring started", self.name);
+        loop {
+            self.poll(db_service, sinks).await;
+            debug!("[{}] Sleeping for {:?}...", self.name, interval);
+            sleep(interval).await;
+        }
+    }
+
+    async fn poll(&mut self, db_service: &DbService, sinks: &Sinks) {
+        debug!("[{}] Fetching power data...", self.name);
+        let result = retry(|| self.api_service.fetch_data(), 3, Duration::from_secs(2)).await;
+        if !matches!(result, Ok(Some(_))) {
+            metrics().record_fetch_failure(&self.name);
+            if let Some(mqtt) = &sinks.mqtt {
+                mqtt.set_available(&self.name, false);
+            }
+        }
+
+        match result {
+            Ok(Some(data)) => {
+                debug!(
+                    "[{}] Data fetched successfully: room={}, money={:.2}, energy={:.2}",
+                    self.name, data.room_display_name, data.remaining_money, data.remaining_energy
+                );
+
+                metrics().record_fetch_success(&self.name, &data);
+
+                // Reset consecutive failure counter on success
+                if let Some(manager) = &mut self.notification_manager {
+                    manager.reset_fetch_failures().await;
+                }
+
+                // compare with the previous record before it is superseded
+                let recharge_amount = detect_recharge(&self.name, db_service, &data).await;
+
+                // save data to database
+                if let Err(e) = db_service.save_data(&data).await {
+                    error!("[{}] Failed to save data: {}", self.name, e);
+                }
+                if let Some(influx) = &sinks.influx {
+                    influx.record(&data);
+                }
+                if let Some(mqtt) = &sinks.mqtt {
+                    mqtt.publish_state(&self.name, &data);
+                }
+
+                // notify logic
+                if let Some(manager) = &mut self.notification_manager {
+                    let forecast = estimate_consumption(
+                        &self.name,
+                        db_service,
+                        manager.forecast_window_days(),
+                        &data,
+                    )
+                    .await;
+                    let report = PowerReport {
+                        info: data,
+                        forecast,
+                        recharge_amount,
+                        alert: None,
+                    };
+                    debug!("[{}] Checking notification conditions...", self.name);
+                    manager.check_and_notify(&report).await;
+                }
+            }
+            Ok(None) => {
+                debug!(
+                    "[{}] No data returned from API (details logged above)",
+                    self.name
+                );
+                // Record as a fetch failure
+                if let Some(manager) = &mut self.notification_manager {
+                    manager.record_fetch_failure().await;
+                }
+            }
+            Err(e) => {
+                error!("[{}] Failed to fetch data: {}", self.name, e);
+                // Record consecutive fetch failure
+                if let Some(manager) = &mut self.notification_manager {
+                    manager.record_fetch_failure().await;
+                }
+            }
+        }
+    }
+}
+
+/// Detects a top-up by comparing the fetched balance with the previous record,
+/// storing it in the `recharges` table. Returns the recharged amount.
+async fn detect_recharge(name: &str, db_service: &DbService, data: &PowerInfo) -> Option<f64> {
+    let previous = match db_service.latest_record(&data.room_id).await {
+        Ok(previous) => previous?,
+        Err(e) => {
+            error!("[{}] Failed to load previous record: {}", name, e);
+            return None;
+        }
+    };
+
+    let amount = data.remaining_money - previous.remaining_money;
+    if amount < MIN_RECHARGE_AMOUNT {
+        return None;
+    }
+
+    info!(
+        "[{}] Recharge detected: room={}, amount={:.2}, balance {:.2} -> {:.2}",
+        name, data.room_display_name, amount, previous.remaining_money, data.remaining_money
+    );
+    if let Err(e) = db_service
+        .save_recharge(data, amount, previous.remaining_money)
+        .await
+    {
+        error!("[{}] Failed to save recharge: {}", name, e);
+    }
+    Some(amount)
+}
+
+async fn estimate_consumption(
+    name: &str,
+    db_service: &DbService,
+    window_days: u32,
+    data: &PowerInfo,
+) -> Option<ConsumptionForecast> {
+    let since = Utc::now() - chrono::Duration::days(window_days as i64);
+    match db_service
+        .history(Some(&data.room_id), Some(since), None, i64::MAX)
+        .await
+    {
+        Ok(records) => {
+            let forecast = forecast::estimate(&records, data.remaining_money);
+            debug!("[{}] Consumption forecast: {:?}", name, forecast);
+            forecast
+        }
+        Err(e) => {
+            error!("[{}] Failed to load history for forecast: {}", name, e);
+            None
+        }
+    }
+}
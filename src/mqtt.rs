@@ -0,0 +1,421 @@
+use crate::api::PowerInfo;
+use crate::config::MqttConfig;
+use chrono::Utc;
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+const ONLINE: &str = "online";
+const OFFLINE: &str = "offline";
+
+/// Publishes samples to an MQTT broker, announcing each room to Home Assistant
+/// through MQTT discovery the first time it is seen, whenever the account
+/// fetching it changes, and again when Home Assistant comes back online.
+///
+/// Topics, relative to `topic_prefix`:
+/// - `status`: `online` / `offline` for the whole process (last will)
+/// - `<account>/availability`: `online` / `offline` after each fetch
+/// - `<room_id>/state`: JSON sample, retained
+#[derive(Clone)]
+pub struct MqttPublisher {
+    client: AsyncClient,
+    topic_prefix: String,
+    discovery_prefix: Option<String>,
+    /// Rooms announced through discovery, by topic segment
+    rooms: Arc<Mutex<HashMap<String, DiscoveredRoom>>>,
+}
+
+/// What the discovery config of a room was last published with.
+#[derive(Clone, PartialEq)]
+struct DiscoveredRoom {
+    account: String,
+    name: String,
+}
+
+impl MqttPublisher {
+    /// Connects to the broker in the background. Returns `None` if disabled.
+    pub fn spawn(config: &MqttConfig) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        if config.host.is_empty() {
+            return Err("mqtt.host is required when mqtt.enabled = true".into());
+        }
+
+        let topic_prefix = config.topic_prefix.trim_end_matches('/').to_string();
+        let status_topic = format!("{}/status", topic_prefix);
+
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        options.set_last_will(LastWill::new(
+            &status_topic,
+            OFFLINE,
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if !config.username.is_empty() {
+            options.set_credentials(&config.username, &config.password);
+        }
+
+        let (client, mut event_loop) = AsyncClient::new(options, 100);
+        let publisher = Self {
+            client,
+            discovery_prefix: config
+                .discovery_enabled
+                .then(|| config.discovery_prefix.trim_end_matches('/').to_string()),
+            topic_prefix: topic_prefix.clone(),
+            rooms: Arc::new(Mutex::new(HashMap::new())),
+        };
+        // Home Assistant publishes `online` here when it starts, and needs the
+        // discovery configs again if they were lost (e.g. a non-persistent broker)
+        let birth_topic = publisher
+            .discovery_prefix
+            .as_ref()
+            .map(|prefix| format!("{}/status", prefix));
+
+        let listener = publisher.clone();
+        tokio::spawn(async move {
+            loop {
+                match event_loop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("Connected to MQTT broker");
+                        listener.publish(&status_topic, ONLINE.to_string());
+                        // subscriptions do not survive a clean session reconnect
+                        let subscribed = birth_topic
+                            .as_ref()
+                            .map(|topic| listener.client.try_subscribe(topic, QoS::AtLeastOnce));
+                        if let Some(Err(e)) = subscribed {
+                            warn!("Failed to subscribe to Home Assistant status: {}", e);
+                        }
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        if birth_topic.as_deref() == Some(publish.topic.as_str())
+                            && publish.payload.as_ref() == ONLINE.as_bytes()
+                        {
+                            info!("Home Assistant is online, republishing discovery");
+                            listener.republish_discovery();
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        // the event loop reconnects on the next poll
+                        warn!("MQTT connection error: {}. Reconnecting in 5s...", e);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+        info!(
+            "MQTT publisher enabled ({}:{}, topic prefix '{}')",
+            config.host, config.port, topic_prefix
+        );
+
+        Ok(Some(publisher))
+    }
+
+    /// Publishes a fetched sample and marks the account as available.
+    pub fn publish_state(&self, account: &str, info: &PowerInfo) {
+        let room = topic_segment(&info.room_id);
+        let discovered = DiscoveredRoom {
+            account: account.to_string(),
+            name: info.room_display_name.clone(),
+        };
+        let previous = self
+            .rooms
+            .lock()
+            .unwrap()
+            .insert(room.clone(), discovered.clone());
+        // the availability list follows the account that currently fetches the room
+        if previous.as_ref() != Some(&discovered) {
+            self.publish_discovery(&room, &discovered);
+        }
+
+        self.publish(
+            &self.state_topic(&room),
+            state_payload(account, info).to_string(),
+        );
+        self.set_available(account, true);
+    }
+
+    /// Marks the account's sensors as unavailable (or available again).
+    pub fn set_available(&self, account: &str, available: bool) {
+        self.publish(
+            &self.availability_topic(account),
+            availability_payload(available).to_string(),
+        );
+    }
+
+    /// Publishes the discovery configs of every room seen so far.
+    fn republish_discovery(&self) {
+        let rooms = self.rooms.lock().unwrap().clone();
+        for (room, discovered) in &rooms {
+            self.publish_discovery(room, discovered);
+        }
+    }
+
+    fn publish_discovery(&self, room: &str, discovered: &DiscoveredRoom) {
+        let configs = self.discovery_configs(room, discovered);
+        if configs.is_empty() {
+            return;
+        }
+        debug!(
+            "Publishing Home Assistant discovery for room {} (account {})",
+            room, discovered.account
+        );
+        for (topic, config) in configs {
+            self.publish(&topic, config.to_string());
+        }
+    }
+
+    /// The discovery topics and configs of the sensors of a room, none with
+    /// discovery disabled.
+    fn discovery_configs(
+        &self,
+        room: &str,
+        discovered: &DiscoveredRoom,
+    ) -> Vec<(String, serde_json::Value)> {
+        let Some(discovery_prefix) = &self.discovery_prefix else {
+            return Vec::new();
+        };
+
+        let device = json!({
+            "identifiers": [format!("uestc_power_{}", room)],
+            "name": discovered.name,
+            "manufacturer": "UESTC",
+            "model": "Dormitory Power Meter",
+        });
+        let availability = json!([
+            { "topic": format!("{}/status", self.topic_prefix) },
+            { "topic": self.availability_topic(&discovered.account) },
+        ]);
+        let sensors = [
+            (
+                "balance",
+                "Balance",
+                json!({
+                    "device_class": "monetary",
+                    "unit_of_measurement": "CNY",
+                    "value_template": "{{ value_json.remaining_money }}",
+                    "icon": "mdi:cash",
+                }),
+            ),
+            (
+                "energy",
+                "Remaining Energy",
+                json!({
+                    "state_class": "measurement",
+                    "unit_of_measurement": "kWh",
+                    "value_template": "{{ value_json.remaining_energy }}",
+                    "icon": "mdi:flash",
+                }),
+            ),
+        ];
+
+        sensors
+            .into_iter()
+            .map(|(key, name, mut config)| {
+                let object_id = format!("uestc_power_{}_{}", room, key);
+                config["name"] = json!(name);
+                config["unique_id"] = json!(object_id);
+                config["state_topic"] = json!(self.state_topic(room));
+                config["json_attributes_topic"] = json!(self.state_topic(room));
+                config["availability"] = availability.clone();
+                config["availability_mode"] = json!("all");
+                config["device"] = device.clone();
+                (
+                    format!("{}/sensor/{}/config", discovery_prefix, object_id),
+                    config,
+                )
+            })
+            .collect()
+    }
+
+    fn state_topic(&self, room: &str) -> String {
+        format!("{}/{}/state", self.topic_prefix, room)
+    }
+
+    fn availability_topic(&self, account: &str) -> String {
+        format!(
+            "{}/{}/availability",
+            self.topic_prefix,
+            topic_segment(account)
+        )
+    }
+
+    /// Queues a retained message without waiting, so a broker outage never
+    /// blocks the polling loop.
+    fn publish(&self, topic: &str, payload: String) {
+        if let Err(e) = self
+            .client
+            .try_publish(topic, QoS::AtLeastOnce, true, payload)
+        {
+            warn!("Failed to queue MQTT message for {}: {}", topic, e);
+        }
+    }
+}
+
+/// The retained sample published on `<room_id>/state`.
+fn state_payload(account: &str, info: &PowerInfo) -> serde_json::Value {
+    json!({
+        "remaining_money": info.remaining_money,
+        "remaining_energy": info.remaining_energy,
+        "room_id": info.room_id,
+        "room_name": info.room_display_name,
+        "building_id": info.building_id,
+        "campus_id": info.campus_id,
+        "room_number": info.room_number,
+        "account": account,
+        "updated_at": Utc::now().to_rfc3339(),
+    })
+}
+
+fn availability_payload(available: bool) -> &'static str {
+    if available { ONLINE } else { OFFLINE }
+}
+
+/// Keeps a value usable as a single topic level and Home Assistant object id.
+fn topic_segment(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn publisher(discovery_prefix: Option<&str>) -> MqttPublisher {
+        // never polled, nothing is sent
+        let (client, _) = AsyncClient::new(MqttOptions::new("test", "localhost", 1883), 10);
+        MqttPublisher {
+            client,
+            topic_prefix: "uestc_power".to_string(),
+            discovery_prefix: discovery_prefix.map(str::to_string),
+            rooms: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn sample() -> PowerInfo {
+        serde_json::from_value(json!({
+            "retcode": 0,
+            "msg": "",
+            "sydl": "26.9",
+            "syje": "14.4",
+            "dffjbh": "m1",
+            "roomName": "Room 101",
+            "roomId": "r1",
+            "buiId": "b1",
+            "areaid": "a1",
+            "fjh": "101",
+        }))
+        .unwrap()
+    }
+
+    fn room(account: &str) -> DiscoveredRoom {
+        DiscoveredRoom {
+            account: account.to_string(),
+            name: "Room 101".to_string(),
+        }
+    }
+
+    #[test]
+    fn topics_use_sanitized_segments() {
+        let publisher = publisher(Some("homeassistant"));
+        assert_eq!(publisher.state_topic("r1"), "uestc_power/r1/state");
+        assert_eq!(
+            publisher.availability_topic("alice@uestc.edu/2"),
+            "uestc_power/alice_uestc_edu_2/availability"
+        );
+        assert_eq!(topic_segment("room 1#+"), "room_1__");
+    }
+
+    #[test]
+    fn availability_payloads() {
+        assert_eq!(availability_payload(true), "online");
+        assert_eq!(availability_payload(false), "offline");
+    }
+
+    #[test]
+    fn state_payload_carries_the_sample() {
+        let info = sample();
+        let payload = state_payload("alice", &info);
+        assert_eq!(payload["remaining_money"], 14.4);
+        assert_eq!(payload["remaining_energy"], 26.9);
+        assert_eq!(payload["room_id"], "r1");
+        assert_eq!(payload["room_name"], "Room 101");
+        assert_eq!(payload["account"], "alice");
+        assert!(payload["updated_at"].is_string());
+    }
+
+    #[test]
+    fn discovery_configs_describe_both_sensors() {
+        let publisher = publisher(Some("homeassistant"));
+        let configs = publisher.discovery_configs("r1", &room("alice"));
+        let topics: Vec<_> = configs.iter().map(|(topic, _)| topic.as_str()).collect();
+        assert_eq!(
+            topics,
+            [
+                "homeassistant/sensor/uestc_power_r1_balance/config",
+                "homeassistant/sensor/uestc_power_r1_energy/config",
+            ]
+        );
+
+        let balance = &configs[0].1;
+        assert_eq!(balance["unique_id"], "uestc_power_r1_balance");
+        assert_eq!(balance["device_class"], "monetary");
+        assert_eq!(balance["unit_of_measurement"], "CNY");
+        assert_eq!(
+            balance["value_template"],
+            "{{ value_json.remaining_money }}"
+        );
+        assert_eq!(balance["state_topic"], "uestc_power/r1/state");
+        assert_eq!(balance["device"]["identifiers"][0], "uestc_power_r1");
+        assert_eq!(balance["device"]["name"], "Room 101");
+        assert_eq!(balance["availability_mode"], "all");
+        assert_eq!(
+            balance["availability"],
+            json!([
+                { "topic": "uestc_power/status" },
+                { "topic": "uestc_power/alice/availability" },
+            ])
+        );
+
+        let energy = &configs[1].1;
+        assert_eq!(energy["unit_of_measurement"], "kWh");
+        assert_eq!(energy["state_class"], "measurement");
+    }
+
+    #[test]
+    fn rooms_follow_the_account_fetching_them() {
+        let publisher = publisher(Some("homeassistant"));
+        let info = sample();
+        let account =
+            |publisher: &MqttPublisher| publisher.rooms.lock().unwrap()["r1"].account.clone();
+
+        publisher.publish_state("alice", &info);
+        assert_eq!(account(&publisher), "alice");
+        publisher.publish_state("bob", &info);
+        assert_eq!(account(&publisher), "bob");
+        let configs = publisher.discovery_configs("r1", &publisher.rooms.lock().unwrap()["r1"]);
+        assert_eq!(
+            configs[0].1["availability"][1]["topic"],
+            "uestc_power/bob/availability"
+        );
+    }
+
+    #[test]
+    fn discovery_disabled_has_no_configs() {
+        let publisher = publisher(None);
+        assert!(publisher.discovery_configs("r1", &room("alice")).is_empty());
+    }
+}
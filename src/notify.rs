@@ -1,54 +1,712 @@
 use crate::api::PowerInfo;
-use crate::config::{NotifyConfig, NotifyType};
+use crate::config::{
+    AlertLevel, BarkLevel, Locale, NotifyConfig, NotifyType, QuietHours, ThresholdTier,
+};
+use crate::db::{DbService, NewOutboxEntry, NotificationAttempt, NotificationState, OutboxEntry};
+use crate::forecast::ConsumptionForecast;
+use crate::metrics::metrics;
+use crate::template::{
+    ErrorDetails, MessageTemplates, PowerContext, WebhookTemplate, error_message,
+};
 use crate::utils::retry;
-use chrono::{Local, Timelike};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chrono::{Local, Timelike, Utc};
+use futures::future::join_all;
+use hmac::{Hmac, Mac};
 use lettre::transport::smtp::client::{Tls, TlsParameters};
 use lettre::{
     AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
     message::{Message, header::ContentType},
     transport::smtp::authentication::Credentials,
 };
+use serde::{Deserialize, Serialize};
 use serde_json;
-use std::collections::HashMap;
+use sha2::Sha256;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::future::Future;
 use std::net::IpAddr;
 use std::net::ToSocketAddrs;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
+/// Attempts per notifier before a notification goes to the outbox.
+const DELIVERY_ATTEMPTS: usize = 3;
+/// How often the outbox is checked for notifications due for redelivery.
+const OUTBOX_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Delay before the first redelivery, doubled after every failed one.
+const OUTBOX_INITIAL_BACKOFF_MINUTES: i64 = 1;
+const OUTBOX_MAX_BACKOFF_MINUTES: i64 = 60;
+/// How long notifications deferred by quiet hours are kept at least once the
+/// window ends, even with the outbox disabled.
+const DEFERRED_MIN_AGE_MINUTES: u64 = 60;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum NotificationEvent {
     LowBalance,
     Heartbeat,
     LoginFailure,
     ConsecutiveFetchFailures,
+    DepletionForecast,
+    Recharge,
+}
+
+impl NotificationEvent {
+    pub const ALL: [NotificationEvent; 6] = [
+        NotificationEvent::LowBalance,
+        NotificationEvent::Heartbeat,
+        NotificationEvent::LoginFailure,
+        NotificationEvent::ConsecutiveFetchFailures,
+        NotificationEvent::DepletionForecast,
+        NotificationEvent::Recharge,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationEvent::LowBalance => "low_balance",
+            NotificationEvent::Heartbeat => "heartbeat",
+            NotificationEvent::LoginFailure => "login_failure",
+            NotificationEvent::ConsecutiveFetchFailures => "consecutive_fetch_failures",
+            NotificationEvent::DepletionForecast => "depletion_forecast",
+            NotificationEvent::Recharge => "recharge",
+        }
+    }
+
+    /// Error events carry a message instead of a power report and are sent
+    /// through `Notifier::notify_error`.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            NotificationEvent::LoginFailure | NotificationEvent::ConsecutiveFetchFailures
+        )
+    }
+}
+
+impl std::str::FromStr for NotificationEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|event| event.as_str() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|e| e.as_str()).collect();
+                format!(
+                    "unknown event '{}', expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// A fetched sample together with the statistics derived from its history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerReport {
+    #[serde(flatten)]
+    pub info: PowerInfo,
+    pub forecast: Option<ConsumptionForecast>,
+    /// Amount recharged since the previous sample, if a top-up was detected
+    pub recharge_amount: Option<f64>,
+    /// Low balance tier that was crossed, set for low balance notifications
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert: Option<LowBalanceAlert>,
+}
+
+impl PowerReport {
+    /// Whether the report carries a critical low balance alert.
+    pub fn is_critical(&self) -> bool {
+        self.alert
+            .as_ref()
+            .is_some_and(|alert| alert.level == AlertLevel::Critical)
+    }
+}
+
+/// The low balance tier behind a notification, with its resolved priorities.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LowBalanceAlert {
+    pub level: AlertLevel,
+    pub threshold: f64,
+    #[serde(skip)]
+    pub pushover_priority: i8,
+    #[serde(skip)]
+    pub ntfy_priority: u8,
+}
+
+impl From<&ThresholdTier> for LowBalanceAlert {
+    fn from(tier: &ThresholdTier) -> Self {
+        Self {
+            level: tier.level,
+            threshold: tier.threshold,
+            pushover_priority: tier
+                .pushover_priority
+                .unwrap_or_else(|| tier.level.pushover_priority()),
+            ntfy_priority: tier
+                .ntfy_priority
+                .unwrap_or_else(|| tier.level.ntfy_priority()),
+        }
+    }
+}
+
+impl LowBalanceAlert {
+    /// Resolves the priorities again after deserializing, from the tier with
+    /// the same threshold or else from the level.
+    fn resolve(self, tiers: &[ThresholdTier]) -> Self {
+        match tiers.iter().find(|tier| tier.threshold == self.threshold) {
+            Some(tier) => Self::from(tier),
+            None => Self {
+                pushover_priority: self.level.pushover_priority(),
+                ntfy_priority: self.level.ntfy_priority(),
+                ..self
+            },
+        }
+    }
+}
+
+/// What a notification sends. Failed ones are stored as JSON in the outbox.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum NotificationPayload {
+    Report(Box<PowerReport>),
+    Error(String),
+}
+
+impl NotificationPayload {
+    /// Critical low balance alerts are exempt from quiet hours with
+    /// `allow_critical`.
+    fn is_critical(&self) -> bool {
+        match self {
+            NotificationPayload::Report(report) => report.is_critical(),
+            NotificationPayload::Error(_) => false,
+        }
+    }
+}
+
+/// A notifier together with the channel it was built for.
+struct ChannelNotifier {
+    name: String,
+    notify_type: NotifyType,
+    notifier: Box<dyn Notifier>,
+    quiet_hours: Option<QuietHours>,
+}
+
+impl ChannelNotifier {
+    /// When the quiet hours of the channel hold the payload back, the time
+    /// they end.
+    fn deferred_until(
+        &self,
+        payload: &NotificationPayload,
+        now: chrono::DateTime<Local>,
+    ) -> Option<chrono::DateTime<Local>> {
+        let quiet = self.quiet_hours.as_ref()?;
+        if !quiet.defers(now.time(), payload.is_critical()) {
+            return None;
+        }
+        Some(quiet.next_end(now))
+    }
+}
+
+/// Sends notifications through the channels of an account. Channels are
+/// served concurrently, each within `timeout` including its retries, so a
+/// slow one does not hold up the others.
+struct Delivery {
+    notifiers: Vec<ChannelNotifier>,
+    store: Option<StateStore>,
+    timeout: Duration,
+    outbox_max_age_minutes: u64,
+    /// Outbox entries of dispatched notifications the dispatcher has not
+    /// delivered yet, left alone by the outbox worker
+    in_flight: Mutex<HashSet<i64>>,
+}
+
+impl Delivery {
+    /// Sends the payload to the notifiers at `channels` (indexes into
+    /// `notifiers`). Returns false if any of them failed.
+    async fn deliver(
+        &self,
+        event: NotificationEvent,
+        payload: &NotificationPayload,
+        channels: &[usize],
+    ) -> bool {
+        let results = join_all(
+            channels
+                .iter()
+                .map(|&index| self.deliver_to(&self.notifiers[index], event, payload)),
+        )
+        .await;
+        results.into_iter().all(|sent| sent)
+    }
+
+    /// Writes a notification about to be dispatched to the outbox, so it is
+    /// sent after a restart if the program stops before delivering it.
+    /// Returns the outbox entry, none without a store or if it could not be
+    /// written.
+    async fn hold(
+        &self,
+        channel: &ChannelNotifier,
+        event: NotificationEvent,
+        payload: &NotificationPayload,
+    ) -> Option<i64> {
+        let store = self.store.as_ref()?;
+        let now = Utc::now();
+        let max_age_minutes = self.outbox_max_age_minutes.max(DEFERRED_MIN_AGE_MINUTES);
+        let id = store
+            .enqueue(
+                channel,
+                event,
+                payload,
+                None,
+                now + outbox_backoff(0),
+                now + chrono::Duration::minutes(max_age_minutes as i64),
+            )
+            .await?;
+        self.in_flight.lock().unwrap().insert(id);
+        Some(id)
+    }
+
+    /// Delivers a dispatched notification, then removes the outbox entries
+    /// that held it.
+    async fn deliver_dispatch(&self, dispatch: &Dispatch) {
+        join_all(
+            dispatch
+                .channels
+                .iter()
+                .map(|&(index, outbox_id)| async move {
+                    self.deliver_to(&self.notifiers[index], dispatch.event, &dispatch.payload)
+                        .await;
+                    let Some((store, id)) = self.store.as_ref().zip(outbox_id) else {
+                        return;
+                    };
+                    if let Err(e) = store.db.delete_outbox(id).await {
+                        error!("Failed to delete outbox entry {}: {}", id, e);
+                    }
+                    self.in_flight.lock().unwrap().remove(&id);
+                }),
+        )
+        .await;
+    }
+
+    fn is_in_flight(&self, outbox_id: i64) -> bool {
+        self.in_flight.lock().unwrap().contains(&outbox_id)
+    }
+
+    async fn deliver_to(
+        &self,
+        channel: &ChannelNotifier,
+        event: NotificationEvent,
+        payload: &NotificationPayload,
+    ) -> bool {
+        // Without a store (e.g. test notifications) there is nowhere to wait
+        let deferral = self
+            .store
+            .as_ref()
+            .zip(channel.deferred_until(payload, Local::now()));
+        if let Some((store, until)) = deferral {
+            let max_age_minutes = self.outbox_max_age_minutes.max(DEFERRED_MIN_AGE_MINUTES);
+            store
+                .defer(
+                    channel,
+                    event,
+                    payload,
+                    until.with_timezone(&Utc),
+                    max_age_minutes,
+                )
+                .await;
+            return true;
+        }
+
+        let mut attempts = 0;
+        let send = tokio::time::timeout(
+            self.timeout,
+            retry(
+                || {
+                    attempts += 1;
+                    match payload {
+                        NotificationPayload::Report(report) => {
+                            channel.notifier.notify(report, event)
+                        }
+                        NotificationPayload::Error(error_msg) => {
+                            channel.notifier.notify_error(error_msg, event)
+                        }
+                    }
+                },
+                DELIVERY_ATTEMPTS,
+                Duration::from_secs(2),
+            ),
+        );
+        let error_class = match send.await {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(error_class(e.as_ref())),
+            Err(_) => Some("timeout".to_string()),
+        };
+        if let Some(class) = &error_class {
+            error!(
+                "Notifier {} failed after {} attempt(s): {} (details redacted)",
+                channel.name, attempts, class
+            );
+            metrics().record_notifier_failure(channel.notify_type.as_str());
+        }
+
+        let Some(store) = &self.store else {
+            return error_class.is_none();
+        };
+        store
+            .record_attempt(channel, event, error_class.as_deref(), attempts - 1)
+            .await;
+        let Some(class) = &error_class else {
+            return true;
+        };
+        if self.outbox_max_age_minutes > 0 {
+            let now = Utc::now();
+            let queued = store
+                .enqueue(
+                    channel,
+                    event,
+                    payload,
+                    Some(class),
+                    now + outbox_backoff(0),
+                    now + chrono::Duration::minutes(self.outbox_max_age_minutes as i64),
+                )
+                .await;
+            if queued.is_some() {
+                info!(
+                    "Queued {} notification to {} for redelivery",
+                    event.as_str(),
+                    channel.name
+                );
+            }
+        }
+        false
+    }
+}
+
+/// A notification handed to the dispatcher task of a manager.
+struct Dispatch {
+    event: NotificationEvent,
+    payload: NotificationPayload,
+    /// Indexes into `Delivery::notifiers`, with the outbox entry holding the
+    /// notification until it is delivered
+    channels: Vec<(usize, Option<i64>)>,
+}
+
+/// Where a notification manager keeps its state, so cooldowns and edge
+/// triggers survive restarts, records every delivery attempt and queues
+/// failed and deferred notifications.
+#[derive(Clone)]
+pub struct StateStore {
+    pub db: DbService,
+    pub account: String,
+}
+
+impl StateStore {
+    /// Adds a delivery attempt to the `notifications` table.
+    async fn record_attempt(
+        &self,
+        channel: &ChannelNotifier,
+        event: NotificationEvent,
+        error_class: Option<&str>,
+        retries: i64,
+    ) {
+        let attempt = NotificationAttempt {
+            account: &self.account,
+            event: event.as_str(),
+            channel: &channel.name,
+            channel_type: channel.notify_type.as_str(),
+            error_class,
+            retries,
+        };
+        if let Err(e) = self.db.save_notification(&attempt).await {
+            error!("Failed to record notification: {}", e);
+        }
+    }
+
+    /// Adds a notification to the outbox, to be sent at `next_attempt_at`.
+    /// Returns the entry id, none if it could not be stored.
+    async fn enqueue(
+        &self,
+        channel: &ChannelNotifier,
+        event: NotificationEvent,
+        payload: &NotificationPayload,
+        error_class: Option<&str>,
+        next_attempt_at: chrono::DateTime<Utc>,
+        expires_at: chrono::DateTime<Utc>,
+    ) -> Option<i64> {
+        let payload = match serde_json::to_string(payload) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Failed to serialize notification for the outbox: {}", e);
+                return None;
+            }
+        };
+        let entry = NewOutboxEntry {
+            account: &self.account,
+            channel: &channel.name,
+            event: event.as_str(),
+            payload: &payload,
+            error_class,
+            next_attempt_at,
+            expires_at,
+        };
+        match self.db.enqueue_outbox(&entry).await {
+            Ok(id) => Some(id),
+            Err(e) => {
+                error!("Failed to add notification to the outbox: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Holds a notification in the outbox until the quiet hours of the
+    /// channel end.
+    async fn defer(
+        &self,
+        channel: &ChannelNotifier,
+        event: NotificationEvent,
+        payload: &NotificationPayload,
+        until: chrono::DateTime<Utc>,
+        max_age_minutes: u64,
+    ) {
+        let expires_at = until + chrono::Duration::minutes(max_age_minutes as i64);
+        if self
+            .enqueue(channel, event, payload, None, until, expires_at)
+            .await
+            .is_some()
+        {
+            info!(
+                "Deferred {} notification to {} until the quiet hours end at {}",
+                event.as_str(),
+                channel.name,
+                until.with_timezone(&Local).format("%H:%M")
+            );
+        }
+    }
+}
+
+/// Delay before the redelivery following `attempts` failed ones.
+fn outbox_backoff(attempts: i64) -> chrono::Duration {
+    let minutes = OUTBOX_INITIAL_BACKOFF_MINUTES << attempts.clamp(0, 6);
+    chrono::Duration::minutes(minutes.min(OUTBOX_MAX_BACKOFF_MINUTES))
+}
+
+/// Background task that redelivers the failed notifications of one account
+/// from the outbox, with exponential backoff, until they are sent or expire.
+/// It also sends the notifications deferred by quiet hours once they end and
+/// those a previous run dispatched but did not deliver.
+struct OutboxWorker {
+    store: StateStore,
+    delivery: Arc<Delivery>,
+    tiers: Vec<ThresholdTier>,
+}
+
+impl OutboxWorker {
+    async fn run(self) {
+        let mut ticker = tokio::time::interval(OUTBOX_POLL_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let entries = match self
+                .store
+                .db
+                .due_outbox(&self.store.account, Utc::now())
+                .await
+            {
+                Ok(entries) => entries,
+                Err(e) => {
+                    error!("Failed to load the notification outbox: {}", e);
+                    continue;
+                }
+            };
+            for entry in entries {
+                if !self.delivery.is_in_flight(entry.id) {
+                    self.redeliver(entry).await;
+                }
+            }
+        }
+    }
+
+    async fn redeliver(&self, entry: OutboxEntry) {
+        let now = Utc::now();
+        if entry.expires_at <= now && entry.error_class.is_none() {
+            error!(
+                "Dropping queued {} notification to {}, it was not sent in time",
+                entry.event, entry.channel
+            );
+            self.delete(entry.id).await;
+            return;
+        }
+        if entry.expires_at <= now {
+            error!(
+                "Dropping undelivered {} notification to {} after {} redelivery attempt(s), last error: {}",
+                entry.event,
+                entry.channel,
+                entry.attempts,
+                entry.error_class.as_deref().unwrap_or("unknown")
+            );
+            self.delete(entry.id).await;
+            return;
+        }
+        let Some(channel) = self
+            .delivery
+            .notifiers
+            .iter()
+            .find(|c| c.name == entry.channel)
+        else {
+            warn!(
+                "Dropping queued notification for channel {}, it is no longer configured",
+                entry.channel
+            );
+            self.delete(entry.id).await;
+            return;
+        };
+        let (Ok(event), Ok(mut payload)) = (
+            entry.event.parse::<NotificationEvent>(),
+            serde_json::from_str::<NotificationPayload>(&entry.payload),
+        ) else {
+            error!("Dropping unreadable outbox entry {}", entry.id);
+            self.delete(entry.id).await;
+            return;
+        };
+        if let NotificationPayload::Report(report) = &mut payload {
+            report.alert = report.alert.take().map(|alert| alert.resolve(&self.tiers));
+        }
+
+        let Some(last_error) = entry.error_class.as_deref() else {
+            // Deferred by quiet hours or left by the dispatcher of a previous
+            // run, sent like a new notification
+            debug!(
+                "Sending queued {} notification to {}",
+                entry.event, entry.channel
+            );
+            self.delivery.deliver_to(channel, event, &payload).await;
+            self.delete(entry.id).await;
+            return;
+        };
+        if let Some(until) = channel.deferred_until(&payload, Local::now()) {
+            let until = until.with_timezone(&Utc);
+            debug!(
+                "Postponing redelivery of {} notification to {} until the quiet hours end",
+                entry.event, entry.channel
+            );
+            if let Err(e) = self
+                .store
+                .db
+                .reschedule_outbox(entry.id, entry.attempts, last_error, until)
+                .await
+            {
+                error!("Failed to reschedule outbox entry {}: {}", entry.id, e);
+            }
+            return;
+        }
+
+        debug!(
+            "Redelivering {} notification to {} (attempt {})",
+            entry.event,
+            entry.channel,
+            entry.attempts + 1
+        );
+        let send = match &payload {
+            NotificationPayload::Report(report) => channel.notifier.notify(report, event),
+            NotificationPayload::Error(error_msg) => {
+                channel.notifier.notify_error(error_msg, event)
+            }
+        };
+        let error_class = match tokio::time::timeout(self.delivery.timeout, send).await {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(error_class(e.as_ref())),
+            Err(_) => Some("timeout".to_string()),
+        };
+        let attempts = entry.attempts + 1;
+        self.store
+            .record_attempt(
+                channel,
+                event,
+                error_class.as_deref(),
+                DELIVERY_ATTEMPTS as i64 - 1 + attempts,
+            )
+            .await;
+
+        match error_class {
+            None => {
+                info!(
+                    "Redelivered {} notification to {}",
+                    entry.event, entry.channel
+                );
+                self.delete(entry.id).await;
+            }
+            Some(class) => {
+                metrics().record_notifier_failure(channel.notify_type.as_str());
+                let next_attempt_at = now + outbox_backoff(attempts);
+                warn!(
+                    "Redelivery of {} notification to {} failed: {} (details redacted), next attempt at {}",
+                    entry.event, entry.channel, class, next_attempt_at
+                );
+                if let Err(e) = self
+                    .store
+                    .db
+                    .reschedule_outbox(entry.id, attempts, &class, next_attempt_at)
+                    .await
+                {
+                    error!("Failed to reschedule outbox entry {}: {}", entry.id, e);
+                }
+            }
+        }
+    }
+
+    async fn delete(&self, id: i64) {
+        if let Err(e) = self.store.db.delete_outbox(id).await {
+            error!("Failed to delete outbox entry {}: {}", id, e);
+        }
+    }
 }
 
 pub struct NotificationManager {
     config: NotifyConfig,
-    notifiers: Vec<Box<dyn Notifier>>,
+    store: Option<StateStore>,
+    delivery: Arc<Delivery>,
+    /// Feeds the task that delivers notifications off the polling loop
+    dispatcher: mpsc::UnboundedSender<Dispatch>,
+    tiers: Vec<ThresholdTier>,
+    /// Index in `tiers` of the lowest tier the balance is currently below
+    active_tier: Option<usize>,
     last_low_balance_notify_time: Option<chrono::DateTime<Local>>,
     last_heartbeat_date: Option<chrono::NaiveDate>,
-    last_balance: Option<f64>,
     consecutive_fetch_failures: u32,
     last_fetch_failure_notify_time: Option<chrono::DateTime<Local>>,
+    last_depletion_notify_time: Option<chrono::DateTime<Local>>,
 }
 
 impl NotificationManager {
-    pub fn new(config: NotifyConfig) -> Option<Self> {
+    /// Builds the notifiers of `config` and starts the task delivering
+    /// notifications. With a `store`, the state saved by a previous run is
+    /// restored, every change is saved back and a background task redelivers
+    /// failed notifications from the outbox.
+    pub async fn new(config: NotifyConfig, store: Option<StateStore>) -> Option<Self> {
         if !config.enabled {
             debug!("Notifications disabled");
             return None;
         }
 
-        let notify_types = config.get_active_notify_types();
+        let channels = match config.channels() {
+            Ok(channels) => channels,
+            Err(e) => {
+                error!("Invalid notification channels: {}", e);
+                return None;
+            }
+        };
         let mut notifiers = Vec::new();
 
-        for notify_type in notify_types {
-            if let Some(notifier) = create_single_notifier(&config, notify_type) {
-                notifiers.push(notifier);
+        for channel in channels {
+            if let Some(notifier) =
+                create_single_notifier(&channel.config, channel.notify_type.clone())
+            {
+                notifiers.push(ChannelNotifier {
+                    name: channel.name,
+                    notify_type: channel.notify_type,
+                    notifier,
+                    quiet_hours: channel.config.quiet_hours,
+                });
             }
         }
 
@@ -57,44 +715,266 @@ impl NotificationManager {
             return None;
         }
 
-        Some(Self {
-            config,
+        let delivery = Arc::new(Delivery {
             notifiers,
+            store: store.clone(),
+            timeout: Duration::from_secs(config.channel_timeout_seconds.max(1)),
+            outbox_max_age_minutes: config.outbox_max_age_minutes,
+            in_flight: Mutex::new(HashSet::new()),
+        });
+        let (dispatcher, mut receiver) = mpsc::unbounded_channel::<Dispatch>();
+        let dispatch_delivery = delivery.clone();
+        tokio::spawn(async move {
+            while let Some(dispatch) = receiver.recv().await {
+                dispatch_delivery.deliver_dispatch(&dispatch).await;
+            }
+        });
+
+        let tiers = config.low_balance_tiers();
+        // Also needed with the outbox disabled, for notifications deferred by
+        // quiet hours or held for the dispatcher
+        if let Some(store) = &store {
+            let worker = OutboxWorker {
+                store: store.clone(),
+                delivery: delivery.clone(),
+                tiers: tiers.clone(),
+            };
+            tokio::spawn(worker.run());
+        }
+
+        let mut manager = Self {
+            tiers,
+            config,
+            store,
+            delivery,
+            dispatcher,
+            active_tier: None,
             last_low_balance_notify_time: None,
             last_heartbeat_date: None,
-            last_balance: None,
             consecutive_fetch_failures: 0,
             last_fetch_failure_notify_time: None,
-        })
+            last_depletion_notify_time: None,
+        };
+        manager.restore_state().await;
+        manager.export_fetch_failures();
+        Some(manager)
     }
 
-    async fn notify_all(&self, data: &PowerInfo, event: NotificationEvent) {
-        for (idx, notifier) in self.notifiers.iter().enumerate() {
-            if retry(|| notifier.notify(data, event), 3, Duration::from_secs(2))
-                .await
-                .is_err()
-            {
-                error!("Notifier {} failed: request error (details redacted)", idx);
+    async fn restore_state(&mut self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let state = match store.db.notification_state(&store.account).await {
+            Ok(Some(state)) => state,
+            Ok(None) => {
+                debug!("No saved notification state for {}", store.account);
+                return;
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to load notification state for {}, starting fresh: {}",
+                    store.account, e
+                );
+                return;
             }
+        };
+        debug!("Restoring notification state: {:?}", state);
+
+        // Map the saved threshold back to a tier, the tiers may have changed since
+        self.active_tier = state.low_balance_threshold.and_then(|threshold| {
+            self.tiers
+                .iter()
+                .rposition(|tier| threshold <= tier.threshold)
+        });
+        self.last_low_balance_notify_time = state
+            .last_low_balance_notify_at
+            .map(|t| t.with_timezone(&Local));
+        self.last_heartbeat_date = state.last_heartbeat_date;
+        self.consecutive_fetch_failures =
+            u32::try_from(state.consecutive_fetch_failures).unwrap_or(0);
+        self.last_fetch_failure_notify_time = state
+            .last_fetch_failure_notify_at
+            .map(|t| t.with_timezone(&Local));
+        self.last_depletion_notify_time = state
+            .last_depletion_notify_at
+            .map(|t| t.with_timezone(&Local));
+    }
+
+    fn state(&self) -> NotificationState {
+        NotificationState {
+            account: self
+                .store
+                .as_ref()
+                .map(|store| store.account.clone())
+                .unwrap_or_default(),
+            low_balance_threshold: self.active_tier.map(|index| self.tiers[index].threshold),
+            last_low_balance_notify_at: self
+                .last_low_balance_notify_time
+                .map(|t| t.with_timezone(&Utc)),
+            last_heartbeat_date: self.last_heartbeat_date,
+            consecutive_fetch_failures: self.consecutive_fetch_failures as i64,
+            last_fetch_failure_notify_at: self
+                .last_fetch_failure_notify_time
+                .map(|t| t.with_timezone(&Utc)),
+            last_depletion_notify_at: self
+                .last_depletion_notify_time
+                .map(|t| t.with_timezone(&Utc)),
         }
     }
 
-    async fn notify_error_all(&self, error_msg: &str, event: NotificationEvent) {
-        for (idx, notifier) in self.notifiers.iter().enumerate() {
-            if retry(
-                || notifier.notify_error(error_msg, event),
-                3,
-                Duration::from_secs(2),
-            )
-            .await
-            .is_err()
-            {
-                error!("Notifier {} failed: request error (details redacted)", idx);
-            }
+    /// Saves the state if it differs from `before`.
+    async fn save_state(&self, before: &NotificationState) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let state = self.state();
+        if state == *before {
+            return;
+        }
+        if let Err(e) = store.db.save_notification_state(&state).await {
+            error!(
+                "Failed to save notification state for {}: {}",
+                store.account, e
+            );
+        }
+    }
+
+    pub fn forecast_window_days(&self) -> u32 {
+        self.config.forecast_window_days
+    }
+
+    /// Whether the event goes to the channel. Channels of a low balance tier
+    /// take priority over `notify.routes`; events without a route go everywhere.
+    fn is_routed(&self, channel: &str, event: NotificationEvent, tier_channels: &[String]) -> bool {
+        if !tier_channels.is_empty() {
+            return tier_channels.iter().any(|c| c == channel);
+        }
+        match self.config.routes.get(event.as_str()) {
+            Some(channels) => channels.iter().any(|c| c == channel),
+            None => true,
+        }
+    }
+
+    /// Indexes of the notifiers the event is routed to, or only those named
+    /// in `tier_channels` when it is not empty.
+    fn routed_channels(&self, event: NotificationEvent, tier_channels: &[String]) -> Vec<usize> {
+        self.delivery
+            .notifiers
+            .iter()
+            .enumerate()
+            .filter(|(_, channel)| {
+                let routed = self.is_routed(&channel.name, event, tier_channels);
+                if !routed {
+                    debug!("Event {} not routed to {}", event.as_str(), channel.name);
+                }
+                routed
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Queues the event for delivery to the notifiers it is routed to, without
+    /// waiting for them. With a store it is written to the outbox first, so
+    /// it is not lost if the program stops before it is delivered. Returns
+    /// false if it could not be written.
+    async fn dispatch(
+        &self,
+        event: NotificationEvent,
+        payload: NotificationPayload,
+        tier_channels: &[String],
+    ) -> bool {
+        let mut channels = Vec::new();
+        for index in self.routed_channels(event, tier_channels) {
+            let outbox_id = self
+                .delivery
+                .hold(&self.delivery.notifiers[index], event, &payload)
+                .await;
+            channels.push((index, outbox_id));
+        }
+        let held = self.store.is_none() || channels.iter().all(|(_, id)| id.is_some());
+        let dispatch = Dispatch {
+            event,
+            payload,
+            channels,
+        };
+        if self.dispatcher.send(dispatch).is_err() {
+            error!(
+                "Notification dispatcher has stopped, {} notification left in the outbox",
+                event.as_str()
+            );
+        }
+        held
+    }
+
+    async fn notify_all(
+        &self,
+        report: &PowerReport,
+        event: NotificationEvent,
+        tier_channels: &[String],
+    ) -> bool {
+        let payload = NotificationPayload::Report(Box::new(report.clone()));
+        self.dispatch(event, payload, tier_channels).await
+    }
+
+    async fn notify_error_all(&self, error_msg: &str, event: NotificationEvent) -> bool {
+        self.dispatch(
+            event,
+            NotificationPayload::Error(error_msg.to_string()),
+            &[],
+        )
+        .await
+    }
+
+    /// Sends a synthetic event through every notifier, or only those it is
+    /// routed to when `routed` is set, bypassing the per-event switches and
+    /// cooldowns. Returns false if any notifier failed.
+    pub async fn send_test(
+        &self,
+        report: &PowerReport,
+        event: NotificationEvent,
+        routed: bool,
+    ) -> bool {
+        info!("Sending test {} notification...", event.as_str());
+        let payload = if event.is_error() {
+            let details = ErrorDetails {
+                test: true,
+                ..Default::default()
+            };
+            NotificationPayload::Error(error_message(self.config.locale, event, &details))
+        } else {
+            NotificationPayload::Report(Box::new(report.clone()))
+        };
+        let channels = if routed {
+            // a low balance alert goes to the channels of its tier, if any
+            let tier_channels = report
+                .alert
+                .as_ref()
+                .and_then(|alert| self.tiers.iter().find(|t| t.threshold == alert.threshold))
+                .map(|tier| tier.channels.as_slice())
+                .unwrap_or_default();
+            self.routed_channels(event, tier_channels)
+        } else {
+            (0..self.delivery.notifiers.len()).collect()
+        };
+        self.delivery.deliver(event, &payload, &channels).await
+    }
+
+    pub async fn check_and_notify(&mut self, report: &PowerReport) {
+        let before = self.state();
+        if self.check_conditions(report).await {
+            self.save_state(&before).await;
+        } else {
+            warn!(
+                "Notification state not saved, a dispatched notification could not be written to the outbox"
+            );
         }
     }
 
-    pub async fn check_and_notify(&mut self, data: &PowerInfo) {
+    /// Returns false if a dispatched notification could not be written to the
+    /// outbox, the state must then not be saved or it would be lost for good.
+    async fn check_conditions(&mut self, report: &PowerReport) -> bool {
+        let mut held = true;
+        let data = &report.info;
         let now = Local::now();
         debug!("Checking notification conditions at {}", now);
 
@@ -105,9 +985,11 @@ impl NotificationManager {
                 let today = now.date_naive();
                 if self.last_heartbeat_date != Some(today) {
                     info!("Sending daily heartbeat...");
-                    self.notify_all(data, NotificationEvent::Heartbeat).await;
+                    held &= self
+                        .notify_all(report, NotificationEvent::Heartbeat, &[])
+                        .await;
                     self.last_heartbeat_date = Some(today);
-                    debug!("Heartbeat sent successfully");
+                    debug!("Heartbeat dispatched");
                 } else {
                     debug!("Heartbeat already sent today");
                 }
@@ -123,79 +1005,170 @@ impl NotificationManager {
         // Low Balance Check
         if self.config.enabled {
             let current_balance = data.remaining_money;
-            let threshold = self.config.threshold;
-            let is_low = current_balance <= threshold;
+            // The lowest tier the balance has fallen to
+            let tier_index = self
+                .tiers
+                .iter()
+                .rposition(|tier| current_balance <= tier.threshold);
+            let is_low = tier_index.is_some();
             debug!(
-                "Balance check: current={:.2}, threshold={:.2}, is_low={}",
-                current_balance, threshold, is_low
+                "Balance check: current={:.2}, tier={:?}",
+                current_balance,
+                tier_index.map(|i| self.tiers[i].threshold)
             );
 
-            let should_notify = if is_low {
-                if let Some(last_b) = self.last_balance {
-                    if last_b > threshold {
-                        // Edge trigger: changed from high to low
-                        debug!("Balance dropped below threshold (edge trigger)");
-                        true
-                    } else {
-                        // Already low, check cooldown
-                        if let Some(last_time) = self.last_low_balance_notify_time {
-                            let elapsed = now.signed_duration_since(last_time);
-                            let should =
-                                elapsed.num_minutes() >= self.config.cooldown_minutes as i64;
-                            debug!(
-                                "Balance still low, cooldown check: elapsed={}min, cooldown={}min, should_notify={}",
-                                elapsed.num_minutes(),
-                                self.config.cooldown_minutes,
-                                should
-                            );
+            let notify_tier = tier_index.filter(|&index| {
+                match (self.active_tier, self.last_low_balance_notify_time) {
+                    (Some(active), Some(last_time)) if index <= active => {
+                        // Still in the same tier (or back up to a higher one), check cooldown
+                        let tier = &self.tiers[index];
+                        let cooldown = tier.cooldown_minutes.unwrap_or(self.config.cooldown_minutes);
+                        let elapsed = now.signed_duration_since(last_time);
+                        let should = elapsed.num_minutes() >= cooldown as i64;
+                        debug!(
+                            "Balance still below {:.2}, cooldown check: elapsed={}min, cooldown={}min, should_notify={}",
+                            tier.threshold,
+                            elapsed.num_minutes(),
+                            cooldown,
                             should
-                        } else {
-                            // Should not happen if logic is correct, but safe fallback
-                            debug!("Balance low but no last notify time (fallback)");
-                            true
-                        }
+                        );
+                        should
+                    }
+                    _ => {
+                        // Edge trigger: first run while low, or crossed down into a lower tier
+                        debug!(
+                            "Balance dropped below {:.2} (edge trigger)",
+                            self.tiers[index].threshold
+                        );
+                        true
                     }
-                } else {
-                    // First run and low
-                    debug!("First run with low balance");
-                    true
                 }
-            } else {
-                debug!("Balance is above threshold, no notification needed");
-                false
-            };
+            });
+            if !is_low {
+                debug!("Balance is above all thresholds, no notification needed");
+            }
 
-            if should_notify {
-                debug!("Sending low balance notification...");
-                self.notify_all(data, NotificationEvent::LowBalance).await;
+            if let Some(index) = notify_tier {
+                let tier = &self.tiers[index];
+                debug!(
+                    "Sending {} low balance notification...",
+                    tier.level.as_str()
+                );
+                let alert_report = PowerReport {
+                    alert: Some(LowBalanceAlert::from(tier)),
+                    ..report.clone()
+                };
+                held &= self
+                    .notify_all(&alert_report, NotificationEvent::LowBalance, &tier.channels)
+                    .await;
                 self.last_low_balance_notify_time = Some(now);
-                debug!("Low balance notification sent successfully");
+                debug!("Low balance notification dispatched");
+            }
+            self.active_tier = tier_index;
+
+            // Recharge Check
+            if self.config.recharge_enabled && report.recharge_amount.is_some() {
+                info!("Sending recharge notification...");
+                held &= self
+                    .notify_all(report, NotificationEvent::Recharge, &[])
+                    .await;
+                debug!("Recharge notification dispatched");
+            }
+
+            // Depletion Forecast Check (only while the balance is still above threshold)
+            if self.config.depletion_alert_enabled && !is_low {
+                held &= self.check_depletion_forecast(report).await;
+            }
+        }
+        held
+    }
+
+    async fn check_depletion_forecast(&mut self, report: &PowerReport) -> bool {
+        let Some(days_left) = report.forecast.as_ref().and_then(|f| f.days_until_empty) else {
+            debug!("No depletion forecast available");
+            self.last_depletion_notify_time = None;
+            return true;
+        };
+
+        let alert_days = self.config.depletion_alert_days;
+        if days_left > alert_days {
+            debug!(
+                "Depletion forecast: {:.1} days left, above alert window of {:.1} days",
+                days_left, alert_days
+            );
+            // Re-arm the edge trigger once the projection recovers (e.g. after a recharge)
+            self.last_depletion_notify_time = None;
+            return true;
+        }
+
+        let now = Local::now();
+        let should_notify = match self.last_depletion_notify_time {
+            Some(last_time) => {
+                let elapsed = now.signed_duration_since(last_time);
+                elapsed.num_minutes() >= self.config.cooldown_minutes as i64
             }
+            None => true,
+        };
 
-            self.last_balance = Some(current_balance);
+        if should_notify {
+            info!(
+                "Sending depletion forecast notification ({:.1} days left)...",
+                days_left
+            );
+            let held = self
+                .notify_all(report, NotificationEvent::DepletionForecast, &[])
+                .await;
+            self.last_depletion_notify_time = Some(now);
+            debug!("Depletion forecast notification dispatched");
+            return held;
         }
+        true
     }
 
-    pub async fn notify_login_failure(&self, error_msg: &str) {
+    pub async fn notify_login_failure(&self, account: &str, error: &str) {
         if !self.config.enabled || !self.config.login_failure_enabled {
             return;
         }
 
+        // Sent right away rather than dispatched, the program may exit next
+        // when no account could log in
         info!("Sending login failure notification...");
-        self.notify_error_all(error_msg, NotificationEvent::LoginFailure)
-            .await;
-        debug!("Login failure notification sent successfully");
+        let event = NotificationEvent::LoginFailure;
+        let details = ErrorDetails {
+            account: Some(account),
+            detail: Some(error),
+            ..Default::default()
+        };
+        let channels = self.routed_channels(event, &[]);
+        let payload =
+            NotificationPayload::Error(error_message(self.config.locale, event, &details));
+        self.delivery.deliver(event, &payload, &channels).await;
+        debug!("Login failure notification sent");
     }
 
     pub async fn record_fetch_failure(&mut self) {
+        let before = self.state();
+        if self.count_fetch_failure().await {
+            self.save_state(&before).await;
+        } else {
+            warn!(
+                "Notification state not saved, a dispatched notification could not be written to the outbox"
+            );
+        }
+    }
+
+    /// Returns false if a dispatched notification could not be written to the
+    /// outbox, like `check_conditions`.
+    async fn count_fetch_failure(&mut self) -> bool {
         self.consecutive_fetch_failures += 1;
+        self.export_fetch_failures();
         debug!(
             "Consecutive fetch failures: {}",
             self.consecutive_fetch_failures
         );
 
         if !self.config.enabled || !self.config.fetch_failure_enabled {
-            return;
+            return true;
         }
 
         if self.consecutive_fetch_failures >= self.config.fetch_failure_threshold {
@@ -212,25 +1185,40 @@ impl NotificationManager {
                     "Sending consecutive fetch failures notification (count: {})...",
                     self.consecutive_fetch_failures
                 );
-                let error_msg = format!(
-                    "Failed to fetch data {} times consecutively",
-                    self.consecutive_fetch_failures
-                );
-                self.notify_error_all(&error_msg, NotificationEvent::ConsecutiveFetchFailures)
-                    .await;
+                let event = NotificationEvent::ConsecutiveFetchFailures;
+                let details = ErrorDetails {
+                    count: Some(self.consecutive_fetch_failures),
+                    ..Default::default()
+                };
+                let error_msg = error_message(self.config.locale, event, &details);
+                let held = self.notify_error_all(&error_msg, event).await;
                 self.last_fetch_failure_notify_time = Some(now);
-                debug!("Consecutive fetch failures notification sent successfully");
+                debug!("Consecutive fetch failures notification dispatched");
+                return held;
             }
         }
+        true
     }
 
-    pub fn reset_fetch_failures(&mut self) {
+    pub async fn reset_fetch_failures(&mut self) {
         if self.consecutive_fetch_failures > 0 {
             debug!(
                 "Resetting consecutive fetch failures counter (was: {})",
                 self.consecutive_fetch_failures
             );
+            let before = self.state();
             self.consecutive_fetch_failures = 0;
+            self.export_fetch_failures();
+            self.save_state(&before).await;
+        }
+    }
+
+    /// Sets the `upm_consecutive_fetch_failures` gauge of the account from
+    /// this counter, the one saved with the notification state.
+    fn export_fetch_failures(&self) {
+        if let Some(store) = &self.store {
+            metrics()
+                .set_consecutive_fetch_failures(&store.account, self.consecutive_fetch_failures);
         }
     }
 }
@@ -238,7 +1226,7 @@ impl NotificationManager {
 pub trait Notifier: Send + Sync {
     fn notify<'a>(
         &'a self,
-        info: &'a PowerInfo,
+        report: &'a PowerReport,
         event: NotificationEvent,
     ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>>;
 
@@ -256,13 +1244,49 @@ pub fn create_single_notifier(
     debug!("Creating notifier of type: {:?}", notify_type);
 
     match notify_type {
-        NotifyType::Console => Some(Box::new(ConsoleNotifier)),
+        NotifyType::Console => Some(Box::new(ConsoleNotifier::new(MessageTemplates::new(
+            config,
+            &notify_type,
+        )))),
         NotifyType::Webhook => {
             if config.webhook_url.is_empty() {
                 warn!("Webhook notifier skipped: webhook_url is not configured");
                 return None;
             }
-            Some(Box::new(WebhookNotifier::new(config.webhook_url.clone())))
+            let method =
+                optional_string(&config.webhook_method).unwrap_or_else(|| "POST".to_string());
+            let Ok(method) = reqwest::Method::from_bytes(method.to_uppercase().as_bytes()) else {
+                warn!(
+                    "Webhook notifier skipped: invalid webhook_method '{}'",
+                    config.webhook_method
+                );
+                return None;
+            };
+            let Ok(headers) = reqwest::header::HeaderMap::try_from(&config.webhook_headers) else {
+                warn!("Webhook notifier skipped: webhook_headers contains an invalid header");
+                return None;
+            };
+            // an invalid template falls back to the JSON payload, like the
+            // message templates of the other channels
+            let body_template = (!config.webhook_body_template.is_empty())
+                .then(|| WebhookTemplate::new(&config.webhook_body_template))
+                .and_then(|template| {
+                    template
+                        .inspect_err(|e| {
+                            warn!(
+                                "Invalid webhook_body_template, sending the JSON payload: {}",
+                                e
+                            )
+                        })
+                        .ok()
+                });
+            Some(Box::new(WebhookNotifier::new(
+                config.webhook_url.clone(),
+                method,
+                headers,
+                body_template,
+                optional_string(&config.webhook_secret),
+            )))
         }
         NotifyType::Telegram => {
             if config.telegram_bot_token.is_empty() || config.telegram_chat_id.is_empty() {
@@ -274,6 +1298,7 @@ pub fn create_single_notifier(
             Some(Box::new(TelegramNotifier::new(
                 config.telegram_bot_token.clone(),
                 config.telegram_chat_id.clone(),
+                MessageTemplates::new(config, &notify_type),
             )))
         }
         NotifyType::Pushover => {
@@ -283,14 +1308,18 @@ pub fn create_single_notifier(
                 );
                 return None;
             }
-            Some(Box::new(PushoverNotifier::new(
-                config.pushover_api_token.clone(),
-                config.pushover_user_key.clone(),
-                config.pushover_priority,
-                config.pushover_retry,
-                config.pushover_expire,
-                optional_string(&config.pushover_url),
-            )))
+            Some(Box::new(
+                PushoverNotifier::new(
+                    config.pushover_api_token.clone(),
+                    config.pushover_user_key.clone(),
+                    config.pushover_priority,
+                    config.pushover_retry,
+                    config.pushover_expire,
+                    optional_string(&config.pushover_url),
+                    MessageTemplates::new(config, &notify_type),
+                )
+                .with_quiet_hours(config.quiet_hours.clone()),
+            ))
         }
         NotifyType::Ntfy => {
             if config.ntfy_topic_url.is_empty() {
@@ -323,16 +1352,20 @@ pub fn create_single_notifier(
                 }
             }
 
-            Some(Box::new(NtfyNotifier::new(
-                topic_url.to_string(),
-                optional_string(&config.ntfy_token),
-                config.ntfy_priority,
-                config.ntfy_tags.clone(),
-                optional_string(&config.ntfy_click_action),
-                optional_string(&config.ntfy_icon),
-                config.ntfy_actions.clone(),
-                config.ntfy_use_markdown,
-            )))
+            Some(Box::new(
+                NtfyNotifier::new(
+                    topic_url.to_string(),
+                    optional_string(&config.ntfy_token),
+                    config.ntfy_priority,
+                    config.ntfy_tags.clone(),
+                    optional_string(&config.ntfy_click_action),
+                    optional_string(&config.ntfy_icon),
+                    config.ntfy_actions.clone(),
+                    config.ntfy_use_markdown,
+                    MessageTemplates::new(config, &notify_type),
+                )
+                .with_quiet_hours(config.quiet_hours.clone()),
+            ))
         }
         NotifyType::Email => match EmailNotifier::new(config) {
             Ok(notifier) => Some(Box::new(notifier)),
@@ -341,6 +1374,129 @@ pub fn create_single_notifier(
                 None
             }
         },
+        NotifyType::Discord => {
+            if config.discord_webhook_url.is_empty() {
+                warn!("Discord notifier skipped: discord_webhook_url is not configured");
+                return None;
+            }
+            Some(Box::new(DiscordNotifier::new(
+                config.discord_webhook_url.clone(),
+                config.locale,
+                MessageTemplates::new(config, &notify_type),
+            )))
+        }
+        NotifyType::Slack => {
+            if config.slack_webhook_url.is_empty() {
+                warn!("Slack notifier skipped: slack_webhook_url is not configured");
+                return None;
+            }
+            Some(Box::new(SlackNotifier::new(
+                config.slack_webhook_url.clone(),
+                config.locale,
+                MessageTemplates::new(config, &notify_type),
+            )))
+        }
+        NotifyType::WeCom => {
+            if config.wecom_webhook_url.is_empty() {
+                warn!("WeCom notifier skipped: wecom_webhook_url is not configured");
+                return None;
+            }
+            Some(Box::new(WeComNotifier::new(
+                config.wecom_webhook_url.clone(),
+                MessageTemplates::new(config, &notify_type),
+            )))
+        }
+        NotifyType::DingTalk => {
+            if config.dingtalk_webhook_url.is_empty() {
+                warn!("DingTalk notifier skipped: dingtalk_webhook_url is not configured");
+                return None;
+            }
+            let Ok(webhook_url) = reqwest::Url::parse(&config.dingtalk_webhook_url) else {
+                warn!("DingTalk notifier skipped: dingtalk_webhook_url is not a valid URL");
+                return None;
+            };
+            Some(Box::new(DingTalkNotifier::new(
+                webhook_url,
+                optional_string(&config.dingtalk_secret),
+                MessageTemplates::new(config, &notify_type),
+            )))
+        }
+        NotifyType::Feishu => {
+            if config.feishu_webhook_url.is_empty() {
+                warn!("Feishu notifier skipped: feishu_webhook_url is not configured");
+                return None;
+            }
+            Some(Box::new(FeishuNotifier::new(
+                config.feishu_webhook_url.clone(),
+                optional_string(&config.feishu_secret),
+                MessageTemplates::new(config, &notify_type),
+            )))
+        }
+        NotifyType::ServerChan => {
+            let Some(send_url) = ServerChanNotifier::send_url(config.serverchan_sendkey.trim())
+            else {
+                warn!("ServerChan notifier skipped: serverchan_sendkey is not configured");
+                return None;
+            };
+            Some(Box::new(ServerChanNotifier::new(
+                send_url,
+                MessageTemplates::new(config, &notify_type),
+            )))
+        }
+        NotifyType::PushPlus => {
+            if config.pushplus_token.is_empty() {
+                warn!("PushPlus notifier skipped: pushplus_token is not configured");
+                return None;
+            }
+            Some(Box::new(PushPlusNotifier::new(
+                config.pushplus_token.clone(),
+                optional_string(&config.pushplus_topic),
+                MessageTemplates::new(config, &notify_type),
+            )))
+        }
+        NotifyType::Gotify => {
+            if config.gotify_server_url.is_empty() || config.gotify_app_token.is_empty() {
+                warn!(
+                    "Gotify notifier skipped: gotify_server_url or gotify_app_token is not configured"
+                );
+                return None;
+            }
+            Some(Box::new(
+                GotifyNotifier::new(
+                    &config.gotify_server_url,
+                    config.gotify_app_token.clone(),
+                    config.gotify_priority,
+                    MessageTemplates::new(config, &notify_type),
+                )
+                .with_quiet_hours(config.quiet_hours.clone()),
+            ))
+        }
+        NotifyType::Apprise => {
+            if config.apprise_api_url.is_empty() {
+                warn!("Apprise notifier skipped: apprise_api_url is not configured");
+                return None;
+            }
+            if config.apprise_urls.is_empty() && config.apprise_config_key.is_empty() {
+                warn!(
+                    "Apprise notifier skipped: neither apprise_urls nor apprise_config_key is configured"
+                );
+                return None;
+            }
+            Some(Box::new(AppriseNotifier::new(
+                config,
+                MessageTemplates::new(config, &notify_type),
+            )))
+        }
+        NotifyType::Bark => {
+            if config.bark_device_key.is_empty() {
+                warn!("Bark notifier skipped: bark_device_key is not configured");
+                return None;
+            }
+            Some(Box::new(
+                BarkNotifier::new(config, MessageTemplates::new(config, &notify_type))
+                    .with_quiet_hours(config.quiet_hours.clone()),
+            ))
+        }
     }
 }
 
@@ -353,6 +1509,103 @@ pub fn create_notifier(config: &NotifyConfig) -> Option<Box<dyn Notifier>> {
     create_single_notifier(config, config.notify_type.clone())
 }
 
+/// A short, loggable category of a notifier error, without URLs or tokens.
+fn error_class(e: &(dyn Error + 'static)) -> String {
+    if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+        return if e.is_timeout() {
+            "timeout".to_string()
+        } else if e.is_connect() {
+            "connect".to_string()
+        } else if let Some(status) = e.status() {
+            format!("http_{}", status.as_u16())
+        } else if e.is_decode() {
+            "decode".to_string()
+        } else {
+            "request".to_string()
+        };
+    }
+    if e.is::<lettre::transport::smtp::Error>() {
+        return "smtp".to_string();
+    }
+    if e.is::<lettre::error::Error>() || e.is::<lettre::address::AddressError>() {
+        return "message".to_string();
+    }
+    if let Some(e) = e.downcast_ref::<ApiError>() {
+        return format!("api_{}", e.code);
+    }
+    "other".to_string()
+}
+
+/// An error reported in the body of a successful HTTP response, as bot and
+/// push APIs like WeCom, DingTalk, Feishu or ServerChan do.
+#[derive(Debug)]
+struct ApiError {
+    code: i64,
+    message: String,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "API error {}: {}", self.code, self.message)
+    }
+}
+
+impl Error for ApiError {}
+
+/// Fails on an HTTP error status or a `code_field` other than `success_code`
+/// in the JSON response body.
+async fn check_api_response(
+    response: reqwest::Response,
+    code_field: &str,
+    message_field: &str,
+    success_code: i64,
+) -> Result<(), Box<dyn Error>> {
+    let body: serde_json::Value = response.error_for_status()?.json().await?;
+    let code = body
+        .get(code_field)
+        .and_then(serde_json::Value::as_i64)
+        .unwrap_or(success_code);
+    if code != success_code {
+        let message = body
+            .get(message_field)
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default();
+        return Err(Box::new(ApiError {
+            code,
+            message: message.to_string(),
+        }));
+    }
+    Ok(())
+}
+
+/// Base64 HMAC-SHA256 of `message` keyed with `key`.
+fn hmac_sha256_base64(key: &[u8], message: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    BASE64.encode(mac.finalize().into_bytes())
+}
+
+/// Lowercase hex HMAC-SHA256, as sent in the webhook `X-Signature` header.
+fn hmac_sha256_hex(key: &[u8], message: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// `X-Signature` of a webhook request: the HMAC of `<timestamp>.<body>`, so
+/// receivers can reject replayed requests by their `X-Signature-Timestamp`.
+fn webhook_signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let message = format!("{}.{}", timestamp, body);
+    format!(
+        "sha256={}",
+        hmac_sha256_hex(secret.as_bytes(), message.as_bytes())
+    )
+}
+
 fn optional_string(value: &str) -> Option<String> {
     let trimmed = value.trim();
     if trimmed.is_empty() {
@@ -415,31 +1668,32 @@ fn create_http_client() -> reqwest::Client {
         .expect("failed to build reqwest client with timeout")
 }
 
-pub struct ConsoleNotifier;
+pub struct ConsoleNotifier {
+    templates: MessageTemplates,
+}
+
+impl ConsoleNotifier {
+    pub fn new(templates: MessageTemplates) -> Self {
+        Self { templates }
+    }
+}
 
 impl Notifier for ConsoleNotifier {
     fn notify<'a>(
         &'a self,
-        info: &'a PowerInfo,
+        report: &'a PowerReport,
         event: NotificationEvent,
     ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
         Box::pin(async move {
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
             match event {
-                NotificationEvent::LowBalance => {
-                    warn!(
-                        "UESTC Power Monitor ⚠️ [Low Power Warning] Room: {}, Money: {:.2} CNY, Energy: {:.2} kWh",
-                        info.room_display_name, info.remaining_money, info.remaining_energy
-                    );
-                }
-                NotificationEvent::Heartbeat => {
-                    info!(
-                        "UESTC Power Monitor ℹ️ [Daily Report] Room: {}, Money: {:.2} CNY, Energy: {:.2} kWh",
-                        info.room_display_name, info.remaining_money, info.remaining_energy
-                    );
-                }
-                NotificationEvent::LoginFailure | NotificationEvent::ConsecutiveFetchFailures => {
-                    // These events use notify_error instead
+                NotificationEvent::LowBalance | NotificationEvent::DepletionForecast => {
+                    warn!("{}", message.body);
                 }
+                _ => info!("{}", message.body),
             }
             Ok(())
         })
@@ -451,60 +1705,122 @@ impl Notifier for ConsoleNotifier {
         event: NotificationEvent,
     ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
         Box::pin(async move {
-            match event {
-                NotificationEvent::LoginFailure => {
-                    error!("UESTC Power Monitor 🔐 [Login Failure] {}", error_msg);
-                }
-                NotificationEvent::ConsecutiveFetchFailures => {
-                    error!("UESTC Power Monitor ❌ [Fetch Failures] {}", error_msg);
-                }
-                NotificationEvent::LowBalance | NotificationEvent::Heartbeat => {
-                    // These events use notify instead
-                }
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
             }
+            error!("{}", self.templates.render_error(error_msg, event).body);
             Ok(())
         })
     }
 }
 
+/// Version of the webhook payload, raised when its fields change incompatibly.
+const WEBHOOK_PAYLOAD_VERSION: u32 = 1;
+
+/// Body of webhook requests, the same for every event: power events carry
+/// the report, with the field names of the message templates, error events
+/// the error message.
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    version: u32,
+    event: &'static str,
+    timestamp: String,
+    report: Option<PowerContext<'a>>,
+    error: Option<&'a str>,
+}
+
 pub struct WebhookNotifier {
     client: reqwest::Client,
     url: String,
+    method: reqwest::Method,
+    headers: reqwest::header::HeaderMap,
+    body_template: Option<WebhookTemplate>,
+    secret: Option<String>,
 }
 
 impl WebhookNotifier {
-    pub fn new(url: String) -> Self {
+    pub fn new(
+        url: String,
+        method: reqwest::Method,
+        headers: reqwest::header::HeaderMap,
+        body_template: Option<WebhookTemplate>,
+        secret: Option<String>,
+    ) -> Self {
         Self {
             client: create_http_client(),
             url,
+            method,
+            headers,
+            body_template,
+            secret,
+        }
+    }
+
+    /// Serializes the payload, through the body template if there is one.
+    fn body(&self, payload: &WebhookPayload<'_>) -> Result<String, Box<dyn Error>> {
+        if let Some(template) = &self.body_template {
+            match template.render(payload) {
+                Ok(body) => return Ok(body),
+                Err(e) => warn!(
+                    "Failed to render webhook body template, sending the JSON payload: {}",
+                    e
+                ),
+            }
+        }
+        Ok(serde_json::to_string(payload)?)
+    }
+
+    async fn send(
+        &self,
+        event: NotificationEvent,
+        report: Option<&PowerReport>,
+        error: Option<&str>,
+    ) -> Result<(), Box<dyn Error>> {
+        let payload = WebhookPayload {
+            version: WEBHOOK_PAYLOAD_VERSION,
+            event: event.as_str(),
+            timestamp: Local::now().to_rfc3339(),
+            report: report.map(PowerContext::new),
+            error,
+        };
+        let body = self.body(&payload)?;
+
+        debug!(
+            "Sending webhook notification: {} {}, event={}",
+            self.method,
+            self.url,
+            event.as_str()
+        );
+        // configured headers may replace the default content type
+        let mut request = self
+            .client
+            .request(self.method.clone(), &self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Event-Type", event.as_str())
+            .headers(self.headers.clone());
+        if let Some(secret) = &self.secret {
+            let timestamp = Utc::now().timestamp();
+            request = request
+                .header("X-Signature-Timestamp", timestamp)
+                .header("X-Signature", webhook_signature(secret, timestamp, &body));
         }
+        request.body(body).send().await?.error_for_status()?;
+        debug!("Webhook notification sent successfully");
+        Ok(())
     }
 }
 
 impl Notifier for WebhookNotifier {
     fn notify<'a>(
         &'a self,
-        info: &'a PowerInfo,
+        report: &'a PowerReport,
         event: NotificationEvent,
     ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
         Box::pin(async move {
-            let event_str = match event {
-                NotificationEvent::LowBalance => "low_balance",
-                NotificationEvent::Heartbeat => "heartbeat",
-                NotificationEvent::LoginFailure | NotificationEvent::ConsecutiveFetchFailures => {
-                    return Ok(()); // These events use notify_error instead
-                }
-            };
-            debug!("Sending webhook notification: event={}", event_str);
-            self.client
-                .post(&self.url)
-                .header("X-Event-Type", event_str)
-                .json(info)
-                .send()
-                .await?
-                .error_for_status()?;
-            debug!("Webhook notification sent successfully");
-            Ok(())
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            self.send(event, Some(report), None).await
         })
     }
 
@@ -514,31 +1830,10 @@ impl Notifier for WebhookNotifier {
         event: NotificationEvent,
     ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
         Box::pin(async move {
-            let event_str = match event {
-                NotificationEvent::LoginFailure => "login_failure",
-                NotificationEvent::ConsecutiveFetchFailures => "consecutive_fetch_failures",
-                NotificationEvent::LowBalance | NotificationEvent::Heartbeat => {
-                    return Ok(()); // These events use notify instead
-                }
-            };
-
-            let payload = serde_json::json!({
-                "title": "UESTC Power Monitor",
-                "event": event_str,
-                "error": error_msg,
-                "timestamp": chrono::Local::now().to_rfc3339(),
-            });
-
-            debug!("Sending webhook error notification: event={}", event_str);
-            self.client
-                .post(&self.url)
-                .header("X-Event-Type", event_str)
-                .json(&payload)
-                .send()
-                .await?
-                .error_for_status()?;
-            debug!("Webhook error notification sent successfully");
-            Ok(())
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            self.send(event, None, Some(error_msg)).await
         })
     }
 }
@@ -547,48 +1842,47 @@ pub struct TelegramNotifier {
     client: reqwest::Client,
     bot_token: String,
     chat_id: String,
+    templates: MessageTemplates,
 }
 
 impl TelegramNotifier {
-    pub fn new(bot_token: String, chat_id: String) -> Self {
+    pub fn new(bot_token: String, chat_id: String, templates: MessageTemplates) -> Self {
         Self {
             client: create_http_client(),
             bot_token,
             chat_id,
+            templates,
         }
     }
+
+    async fn send_message(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.bot_token);
+        let params = [("chat_id", self.chat_id.as_str()), ("text", message)];
+
+        self.client
+            .post(&url)
+            .form(&params)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
 }
 
 impl Notifier for TelegramNotifier {
     fn notify<'a>(
         &'a self,
-        info: &'a PowerInfo,
+        report: &'a PowerReport,
         event: NotificationEvent,
     ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
         Box::pin(async move {
-            let title = match event {
-                NotificationEvent::LowBalance => "⚠️ [Low Power Warning]",
-                NotificationEvent::Heartbeat => "ℹ️ [Daily Report]",
-                NotificationEvent::LoginFailure | NotificationEvent::ConsecutiveFetchFailures => {
-                    return Ok(()); // These events use notify_error instead
-                }
-            };
-
-            let message = format!(
-                "UESTC Power Monitor\n{}\nRoom: {}\nMoney: {:.2} CNY\nEnergy: {:.2} kWh",
-                title, info.room_display_name, info.remaining_money, info.remaining_energy
-            );
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
 
-            let url = format!("https://api.telegram.org/bot{}/sendMessage", self.bot_token);
             debug!("Sending Telegram notification");
-            let params = [("chat_id", &self.chat_id), ("text", &message)];
-
-            self.client
-                .post(&url)
-                .form(&params)
-                .send()
-                .await?
-                .error_for_status()?;
+            self.send_message(&message.body).await?;
             debug!("Telegram notification sent successfully");
             Ok(())
         })
@@ -600,83 +1894,19 @@ impl Notifier for TelegramNotifier {
         event: NotificationEvent,
     ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
         Box::pin(async move {
-            let title = match event {
-                NotificationEvent::LoginFailure => "🔐 [Login Failure]",
-                NotificationEvent::ConsecutiveFetchFailures => "❌ [Fetch Failures]",
-                NotificationEvent::LowBalance | NotificationEvent::Heartbeat => {
-                    return Ok(()); // These events use notify instead
-                }
-            };
-
-            let message = format!("UESTC Power Monitor\n{}\n{}", title, error_msg);
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            let message = self.templates.render_error(error_msg, event);
 
-            let url = format!("https://api.telegram.org/bot{}/sendMessage", self.bot_token);
             debug!("Sending Telegram error notification");
-            let params = [("chat_id", &self.chat_id), ("text", &message)];
-
-            self.client
-                .post(&url)
-                .form(&params)
-                .send()
-                .await?
-                .error_for_status()?;
+            self.send_message(&message.body).await?;
             debug!("Telegram error notification sent successfully");
             Ok(())
         })
     }
 }
 
-fn build_power_notification(
-    info: &PowerInfo,
-    event: NotificationEvent,
-) -> Option<(String, String)> {
-    match event {
-        NotificationEvent::LowBalance => Some((
-            "⚠️ UESTC Power Monitor - Low Balance Warning".to_string(),
-            format!(
-                "Room: {}\nMoney: {:.2} CNY\nEnergy: {:.2} kWh\nTime: {}",
-                info.room_display_name,
-                info.remaining_money,
-                info.remaining_energy,
-                chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
-            ),
-        )),
-        NotificationEvent::Heartbeat => Some((
-            "ℹ️ UESTC Power Monitor - Daily Report".to_string(),
-            format!(
-                "Room: {}\nMoney: {:.2} CNY\nEnergy: {:.2} kWh\nTime: {}",
-                info.room_display_name,
-                info.remaining_money,
-                info.remaining_energy,
-                chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
-            ),
-        )),
-        NotificationEvent::LoginFailure | NotificationEvent::ConsecutiveFetchFailures => None,
-    }
-}
-
-fn build_error_notification(error_msg: &str, event: NotificationEvent) -> Option<(String, String)> {
-    match event {
-        NotificationEvent::LoginFailure => Some((
-            "🔐 UESTC Power Monitor - Login Failure".to_string(),
-            format!(
-                "{}\nTime: {}",
-                error_msg,
-                chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
-            ),
-        )),
-        NotificationEvent::ConsecutiveFetchFailures => Some((
-            "❌ UESTC Power Monitor - Fetch Failures".to_string(),
-            format!(
-                "{}\nTime: {}",
-                error_msg,
-                chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
-            ),
-        )),
-        NotificationEvent::LowBalance | NotificationEvent::Heartbeat => None,
-    }
-}
-
 pub struct PushoverNotifier {
     client: reqwest::Client,
     api_token: String,
@@ -685,6 +1915,8 @@ pub struct PushoverNotifier {
     default_retry: u32,
     default_expire: u32,
     default_url: Option<String>,
+    quiet_hours: Option<QuietHours>,
+    templates: MessageTemplates,
 }
 
 impl PushoverNotifier {
@@ -695,6 +1927,7 @@ impl PushoverNotifier {
         default_retry: u32,
         default_expire: u32,
         default_url: Option<String>,
+        templates: MessageTemplates,
     ) -> Self {
         Self {
             client: create_http_client(),
@@ -704,13 +1937,30 @@ impl PushoverNotifier {
             default_retry,
             default_expire,
             default_url,
+            quiet_hours: None,
+            templates,
         }
     }
 
+    pub fn with_quiet_hours(mut self, quiet_hours: Option<QuietHours>) -> Self {
+        self.quiet_hours = quiet_hours;
+        self
+    }
+
     fn clamp_priority(priority: i8) -> i8 {
         priority.clamp(-2, 2)
     }
 
+    /// Lowers the priority to the quiet hours one while they downgrade.
+    fn quiet_priority(&self, priority: i8, critical: bool) -> i8 {
+        match &self.quiet_hours {
+            Some(quiet) if quiet.downgrades(Local::now().time(), critical) => {
+                priority.min(quiet.pushover_priority)
+            }
+            _ => priority,
+        }
+    }
+
     fn sanitize_emergency_params(retry: u32, expire: u32) -> (u32, u32) {
         let retry = retry.max(30);
         let expire = expire.clamp(30, 10_800);
@@ -765,21 +2015,28 @@ impl PushoverNotifier {
 impl Notifier for PushoverNotifier {
     fn notify<'a>(
         &'a self,
-        info: &'a PowerInfo,
+        report: &'a PowerReport,
         event: NotificationEvent,
     ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
         Box::pin(async move {
-            let Some((title, message)) = build_power_notification(info, event) else {
-                return Ok(());
-            };
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
             let priority = match event {
-                NotificationEvent::LowBalance => 2, // highest/emergency priority
+                // emergency priority unless the tier says otherwise
+                NotificationEvent::LowBalance => report
+                    .alert
+                    .as_ref()
+                    .map_or(2, |alert| alert.pushover_priority),
+                NotificationEvent::DepletionForecast => self.default_priority.max(1), // high priority
                 _ => self.default_priority,
             };
+            let priority = self.quiet_priority(priority, report.is_critical());
 
             self.send_message(
-                &message,
-                Some(&title),
+                &message.body,
+                Some(&message.title),
                 priority,
                 self.default_url.as_deref(),
             )
@@ -794,14 +2051,15 @@ impl Notifier for PushoverNotifier {
         event: NotificationEvent,
     ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
         Box::pin(async move {
-            let Some((title, message)) = build_error_notification(error_msg, event) else {
-                return Ok(());
-            };
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            let message = self.templates.render_error(error_msg, event);
 
             self.send_message(
-                &message,
-                Some(&title),
-                self.default_priority,
+                &message.body,
+                Some(&message.title),
+                self.quiet_priority(self.default_priority, false),
                 self.default_url.as_deref(),
             )
             .await?;
@@ -820,6 +2078,8 @@ pub struct NtfyNotifier {
     icon: Option<String>,
     actions: Vec<serde_json::Value>,
     use_markdown: bool,
+    quiet_hours: Option<QuietHours>,
+    templates: MessageTemplates,
 }
 
 impl NtfyNotifier {
@@ -832,6 +2092,7 @@ impl NtfyNotifier {
         icon: Option<String>,
         actions: Vec<serde_json::Value>,
         use_markdown: bool,
+        templates: MessageTemplates,
     ) -> Self {
         Self {
             client: create_http_client(),
@@ -843,13 +2104,30 @@ impl NtfyNotifier {
             icon,
             actions,
             use_markdown,
+            quiet_hours: None,
+            templates,
         }
     }
 
+    pub fn with_quiet_hours(mut self, quiet_hours: Option<QuietHours>) -> Self {
+        self.quiet_hours = quiet_hours;
+        self
+    }
+
     fn clamp_priority(priority: u8) -> u8 {
         priority.clamp(1, 5)
     }
 
+    /// Lowers the priority to the quiet hours one while they downgrade.
+    fn quiet_priority(&self, priority: u8, critical: bool) -> u8 {
+        match &self.quiet_hours {
+            Some(quiet) if quiet.downgrades(Local::now().time(), critical) => {
+                priority.min(quiet.ntfy_priority)
+            }
+            _ => priority,
+        }
+    }
+
     async fn send_message(
         &self,
         message: &str,
@@ -935,21 +2213,27 @@ impl NtfyNotifier {
 impl Notifier for NtfyNotifier {
     fn notify<'a>(
         &'a self,
-        info: &'a PowerInfo,
+        report: &'a PowerReport,
         event: NotificationEvent,
     ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
         Box::pin(async move {
-            let Some((title, message)) = build_power_notification(info, event) else {
-                return Ok(());
-            };
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
             let priority = match event {
-                NotificationEvent::LowBalance => 5, // highest priority
+                // highest priority unless the tier says otherwise
+                NotificationEvent::LowBalance => {
+                    report.alert.as_ref().map_or(5, |alert| alert.ntfy_priority)
+                }
+                NotificationEvent::DepletionForecast => self.default_priority.max(4), // high priority
                 _ => self.default_priority,
             };
+            let priority = self.quiet_priority(priority, report.is_critical());
 
             self.send_message(
-                &message,
-                Some(&title),
+                &message.body,
+                Some(&message.title),
                 priority,
                 Some(&self.default_tags),
                 self.click_action.as_deref(),
@@ -968,14 +2252,15 @@ impl Notifier for NtfyNotifier {
         event: NotificationEvent,
     ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
         Box::pin(async move {
-            let Some((title, message)) = build_error_notification(error_msg, event) else {
-                return Ok(());
-            };
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            let message = self.templates.render_error(error_msg, event);
 
             self.send_message(
-                &message,
-                Some(&title),
-                self.default_priority,
+                &message.body,
+                Some(&message.title),
+                self.quiet_priority(self.default_priority, false),
                 Some(&self.default_tags),
                 self.click_action.as_deref(),
                 self.icon.as_deref(),
@@ -992,6 +2277,7 @@ pub struct EmailNotifier {
     transport: AsyncSmtpTransport<Tokio1Executor>,
     from: String,
     to: Vec<String>,
+    templates: MessageTemplates,
 }
 
 impl EmailNotifier {
@@ -1045,6 +2331,7 @@ impl EmailNotifier {
             transport,
             from: config.smtp_from.clone(),
             to,
+            templates: MessageTemplates::new(config, &NotifyType::Email),
         })
     }
 
@@ -1067,56 +2354,17 @@ impl EmailNotifier {
 impl Notifier for EmailNotifier {
     fn notify<'a>(
         &'a self,
-        info: &'a PowerInfo,
+        report: &'a PowerReport,
         event: NotificationEvent,
     ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
         Box::pin(async move {
-            let (subject, body) = match event {
-                NotificationEvent::LowBalance => {
-                    let subject = "⚠️ UESTC Power Monitor - Low Balance Warning";
-                    let body = format!(
-                        "UESTC Power Monitor - Low Balance Warning\n\
-                        \n\
-                        Room: {}\n\
-                        Remaining Money: {:.2} CNY\n\
-                        Remaining Energy: {:.2} kWh\n\
-                        \n\
-                        Please recharge your power account soon.\n\
-                        \n\
-                        Time: {}",
-                        info.room_display_name,
-                        info.remaining_money,
-                        info.remaining_energy,
-                        chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
-                    );
-                    (subject, body)
-                }
-                NotificationEvent::Heartbeat => {
-                    let subject = "ℹ️ UESTC Power Monitor - Daily Report";
-                    let body = format!(
-                        "UESTC Power Monitor - Daily Report\n\
-                        \n\
-                        Room: {}\n\
-                        Remaining Money: {:.2} CNY\n\
-                        Remaining Energy: {:.2} kWh\n\
-                        \n\
-                        System is running normally.\n\
-                        \n\
-                        Time: {}",
-                        info.room_display_name,
-                        info.remaining_money,
-                        info.remaining_energy,
-                        chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
-                    );
-                    (subject, body)
-                }
-                NotificationEvent::LoginFailure | NotificationEvent::ConsecutiveFetchFailures => {
-                    return Ok(()); // These events use notify_error instead
-                }
-            };
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
 
-            debug!("Sending email notification: subject={}", subject);
-            self.send_email(subject, &body).await?;
+            debug!("Sending email notification: subject={}", message.title);
+            self.send_email(&message.title, &message.body).await?;
             debug!("Email notification sent successfully");
             Ok(())
         })
@@ -1128,51 +2376,1912 @@ impl Notifier for EmailNotifier {
         event: NotificationEvent,
     ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
         Box::pin(async move {
-            let (subject, body) = match event {
-                NotificationEvent::LoginFailure => {
-                    let subject = "🔐 UESTC Power Monitor - Login Failure";
-                    let body = format!(
-                        "UESTC Power Monitor - Login Failure\n\
-                        \n\
-                        Failed to login to the power monitoring service.\n\
-                        \n\
-                        Error: {}\n\
-                        \n\
-                        Please check your credentials and try again.\n\
-                        \n\
-                        Time: {}",
-                        error_msg,
-                        chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
-                    );
-                    (subject, body)
-                }
-                NotificationEvent::ConsecutiveFetchFailures => {
-                    let subject = "❌ UESTC Power Monitor - Fetch Failures";
-                    let body = format!(
-                        "UESTC Power Monitor - Consecutive Fetch Failures\n\
-                        \n\
-                        {}\n\
-                        \n\
-                        The system is unable to fetch power data. This may indicate:\n\
-                        - Network connectivity issues\n\
-                        - Service unavailability\n\
-                        - Authentication problems\n\
-                        \n\
-                        Time: {}",
-                        error_msg,
-                        chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
-                    );
-                    (subject, body)
-                }
-                NotificationEvent::LowBalance | NotificationEvent::Heartbeat => {
-                    return Ok(()); // These events use notify instead
-                }
-            };
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            let message = self.templates.render_error(error_msg, event);
 
-            debug!("Sending email error notification: subject={}", subject);
-            self.send_email(subject, &body).await?;
+            debug!(
+                "Sending email error notification: subject={}",
+                message.title
+            );
+            self.send_email(&message.title, &message.body).await?;
             debug!("Email error notification sent successfully");
             Ok(())
         })
     }
 }
+
+/// Sidebar colors of Discord embeds and Slack attachments.
+const COLOR_CRITICAL: u32 = 0xE74C3C; // red
+const COLOR_WARNING: u32 = 0xF39C12; // orange
+const COLOR_INFO: u32 = 0x3498DB; // blue
+const COLOR_SUCCESS: u32 = 0x2ECC71; // green
+
+/// How a notification should stand out, mapped to the colors, card
+/// templates or message types of the channels that have them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Severity {
+    Critical,
+    Warning,
+    Info,
+    Success,
+}
+
+impl Severity {
+    /// Severity of an event, following the alert level for low balance.
+    fn of(event: NotificationEvent, alert: Option<&LowBalanceAlert>) -> Self {
+        match event {
+            NotificationEvent::LowBalance => {
+                match alert.map_or(AlertLevel::Critical, |a| a.level) {
+                    AlertLevel::Info => Severity::Info,
+                    AlertLevel::Warning => Severity::Warning,
+                    AlertLevel::Critical => Severity::Critical,
+                }
+            }
+            NotificationEvent::DepletionForecast => Severity::Warning,
+            NotificationEvent::Heartbeat => Severity::Info,
+            NotificationEvent::Recharge => Severity::Success,
+            NotificationEvent::LoginFailure | NotificationEvent::ConsecutiveFetchFailures => {
+                Severity::Critical
+            }
+        }
+    }
+
+    /// Side color of Discord embeds and Slack attachments.
+    fn color(self) -> u32 {
+        match self {
+            Severity::Critical => COLOR_CRITICAL,
+            Severity::Warning => COLOR_WARNING,
+            Severity::Info => COLOR_INFO,
+            Severity::Success => COLOR_SUCCESS,
+        }
+    }
+
+    /// Header template of Feishu cards.
+    fn feishu_template(self) -> &'static str {
+        match self {
+            Severity::Critical => "red",
+            Severity::Warning => "orange",
+            Severity::Info => "blue",
+            Severity::Success => "green",
+        }
+    }
+
+    /// Message type of Apprise notifications.
+    fn apprise_type(self) -> &'static str {
+        match self {
+            Severity::Critical => "failure",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+            Severity::Success => "success",
+        }
+    }
+}
+
+/// Room, money and energy of a report as named fields.
+fn power_fields(report: &PowerReport, locale: Locale) -> [(&'static str, String); 3] {
+    let info = &report.info;
+    let (room, money, energy, currency) = match locale {
+        Locale::En => ("Room", "Money", "Energy", "CNY"),
+        Locale::ZhCn => ("房间", "余额", "电量", "元"),
+    };
+    [
+        (room, info.room_display_name.clone()),
+        (money, format!("{:.2} {}", info.remaining_money, currency)),
+        (energy, format!("{:.2} kWh", info.remaining_energy)),
+    ]
+}
+
+pub struct DiscordNotifier {
+    client: reqwest::Client,
+    webhook_url: String,
+    locale: Locale,
+    templates: MessageTemplates,
+}
+
+impl DiscordNotifier {
+    pub fn new(webhook_url: String, locale: Locale, templates: MessageTemplates) -> Self {
+        Self {
+            client: create_http_client(),
+            webhook_url,
+            locale,
+            templates,
+        }
+    }
+
+    async fn send_embed(
+        &self,
+        mut embed: serde_json::Value,
+        body: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        if !body.trim().is_empty() {
+            embed["description"] = serde_json::Value::String(body.to_string());
+        }
+        embed["timestamp"] = serde_json::Value::String(Utc::now().to_rfc3339());
+        let payload = serde_json::json!({
+            "username": "UESTC Power Monitor",
+            "embeds": [embed],
+        });
+
+        self.client
+            .post(&self.webhook_url)
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+impl Notifier for DiscordNotifier {
+    fn notify<'a>(
+        &'a self,
+        report: &'a PowerReport,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
+            let fields: Vec<_> = power_fields(report, self.locale)
+                .into_iter()
+                .map(|(name, value)| serde_json::json!({"name": name, "value": value, "inline": true}))
+                .collect();
+            let embed = serde_json::json!({
+                "title": message.title,
+                "color": Severity::of(event, report.alert.as_ref()).color(),
+                "fields": fields,
+            });
+
+            debug!("Sending Discord notification");
+            self.send_embed(embed, &message.body).await?;
+            debug!("Discord notification sent successfully");
+            Ok(())
+        })
+    }
+
+    fn notify_error<'a>(
+        &'a self,
+        error_msg: &'a str,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            let message = self.templates.render_error(error_msg, event);
+            let embed = serde_json::json!({
+                "title": message.title,
+                "color": Severity::of(event, None).color(),
+            });
+
+            debug!("Sending Discord error notification");
+            self.send_embed(embed, &message.body).await?;
+            debug!("Discord error notification sent successfully");
+            Ok(())
+        })
+    }
+}
+
+pub struct SlackNotifier {
+    client: reqwest::Client,
+    webhook_url: String,
+    locale: Locale,
+    templates: MessageTemplates,
+}
+
+impl SlackNotifier {
+    pub fn new(webhook_url: String, locale: Locale, templates: MessageTemplates) -> Self {
+        Self {
+            client: create_http_client(),
+            webhook_url,
+            locale,
+            templates,
+        }
+    }
+
+    /// Escapes the characters Slack reserves for links and mentions.
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+
+    /// Sends the title as a header block, followed by the body, the fields
+    /// and the time, in an attachment colored by `color`.
+    async fn send_blocks(
+        &self,
+        title: &str,
+        body: &str,
+        fields: &[(&str, String)],
+        color: u32,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut blocks = vec![serde_json::json!({
+            "type": "header",
+            "text": {"type": "plain_text", "text": title, "emoji": true},
+        })];
+        if !body.trim().is_empty() {
+            blocks.push(serde_json::json!({
+                "type": "section",
+                "text": {"type": "mrkdwn", "text": Self::escape(body)},
+            }));
+        }
+        if !fields.is_empty() {
+            let fields: Vec<_> = fields
+                .iter()
+                .map(|(name, value)| {
+                    serde_json::json!({
+                        "type": "mrkdwn",
+                        "text": format!("*{}*\n{}", name, Self::escape(value)),
+                    })
+                })
+                .collect();
+            blocks.push(serde_json::json!({"type": "section", "fields": fields}));
+        }
+        blocks.push(serde_json::json!({
+            "type": "context",
+            "elements": [{
+                "type": "mrkdwn",
+                "text": Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            }],
+        }));
+
+        // `text` is the fallback shown in notifications
+        let payload = serde_json::json!({
+            "text": Self::escape(title),
+            "attachments": [{"color": format!("#{:06x}", color), "blocks": blocks}],
+        });
+        self.client
+            .post(&self.webhook_url)
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+impl Notifier for SlackNotifier {
+    fn notify<'a>(
+        &'a self,
+        report: &'a PowerReport,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
+
+            debug!("Sending Slack notification");
+            self.send_blocks(
+                &message.title,
+                &message.body,
+                &power_fields(report, self.locale),
+                Severity::of(event, report.alert.as_ref()).color(),
+            )
+            .await?;
+            debug!("Slack notification sent successfully");
+            Ok(())
+        })
+    }
+
+    fn notify_error<'a>(
+        &'a self,
+        error_msg: &'a str,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            let message = self.templates.render_error(error_msg, event);
+
+            debug!("Sending Slack error notification");
+            self.send_blocks(
+                &message.title,
+                &message.body,
+                &[],
+                Severity::of(event, None).color(),
+            )
+            .await?;
+            debug!("Slack error notification sent successfully");
+            Ok(())
+        })
+    }
+}
+
+pub struct WeComNotifier {
+    client: reqwest::Client,
+    webhook_url: String,
+    templates: MessageTemplates,
+}
+
+impl WeComNotifier {
+    pub fn new(webhook_url: String, templates: MessageTemplates) -> Self {
+        Self {
+            client: create_http_client(),
+            webhook_url,
+            templates,
+        }
+    }
+
+    async fn send_text(&self, title: &str, body: &str) -> Result<(), Box<dyn Error>> {
+        let payload = serde_json::json!({
+            "msgtype": "text",
+            "text": {"content": format!("{}\n{}", title, body)},
+        });
+        let response = self
+            .client
+            .post(&self.webhook_url)
+            .json(&payload)
+            .send()
+            .await?;
+        check_api_response(response, "errcode", "errmsg", 0).await
+    }
+}
+
+impl Notifier for WeComNotifier {
+    fn notify<'a>(
+        &'a self,
+        report: &'a PowerReport,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
+
+            debug!("Sending WeCom notification");
+            self.send_text(&message.title, &message.body).await?;
+            debug!("WeCom notification sent successfully");
+            Ok(())
+        })
+    }
+
+    fn notify_error<'a>(
+        &'a self,
+        error_msg: &'a str,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            let message = self.templates.render_error(error_msg, event);
+
+            debug!("Sending WeCom error notification");
+            self.send_text(&message.title, &message.body).await?;
+            debug!("WeCom error notification sent successfully");
+            Ok(())
+        })
+    }
+}
+
+pub struct DingTalkNotifier {
+    client: reqwest::Client,
+    webhook_url: reqwest::Url,
+    secret: Option<String>,
+    templates: MessageTemplates,
+}
+
+impl DingTalkNotifier {
+    pub fn new(
+        webhook_url: reqwest::Url,
+        secret: Option<String>,
+        templates: MessageTemplates,
+    ) -> Self {
+        Self {
+            client: create_http_client(),
+            webhook_url,
+            secret,
+            templates,
+        }
+    }
+
+    /// The webhook URL, signed with the secret if there is one: the
+    /// signature is the HMAC-SHA256 of "{timestamp}\n{secret}", keyed with
+    /// the secret, with the timestamp in milliseconds.
+    fn signed_url(&self, timestamp: i64) -> reqwest::Url {
+        let mut url = self.webhook_url.clone();
+        if let Some(secret) = &self.secret {
+            let sign = hmac_sha256_base64(
+                secret.as_bytes(),
+                format!("{}\n{}", timestamp, secret).as_bytes(),
+            );
+            url.query_pairs_mut()
+                .append_pair("timestamp", &timestamp.to_string())
+                .append_pair("sign", &sign);
+        }
+        url
+    }
+
+    async fn send_text(&self, title: &str, body: &str) -> Result<(), Box<dyn Error>> {
+        let payload = serde_json::json!({
+            "msgtype": "text",
+            "text": {"content": format!("{}\n{}", title, body)},
+        });
+        let response = self
+            .client
+            .post(self.signed_url(Utc::now().timestamp_millis()))
+            .json(&payload)
+            .send()
+            .await?;
+        check_api_response(response, "errcode", "errmsg", 0).await
+    }
+}
+
+impl Notifier for DingTalkNotifier {
+    fn notify<'a>(
+        &'a self,
+        report: &'a PowerReport,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
+
+            debug!("Sending DingTalk notification");
+            self.send_text(&message.title, &message.body).await?;
+            debug!("DingTalk notification sent successfully");
+            Ok(())
+        })
+    }
+
+    fn notify_error<'a>(
+        &'a self,
+        error_msg: &'a str,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            let message = self.templates.render_error(error_msg, event);
+
+            debug!("Sending DingTalk error notification");
+            self.send_text(&message.title, &message.body).await?;
+            debug!("DingTalk error notification sent successfully");
+            Ok(())
+        })
+    }
+}
+
+pub struct FeishuNotifier {
+    client: reqwest::Client,
+    webhook_url: String,
+    secret: Option<String>,
+    templates: MessageTemplates,
+}
+
+impl FeishuNotifier {
+    pub fn new(webhook_url: String, secret: Option<String>, templates: MessageTemplates) -> Self {
+        Self {
+            client: create_http_client(),
+            webhook_url,
+            secret,
+            templates,
+        }
+    }
+
+    /// Sends a card with a header colored like the Discord and Slack
+    /// messages. With a secret, the request carries a timestamp (in seconds)
+    /// and the HMAC-SHA256 of an empty message keyed with
+    /// "{timestamp}\n{secret}", which the bot verifies.
+    async fn send_card(
+        &self,
+        title: &str,
+        body: &str,
+        severity: Severity,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut payload = serde_json::json!({
+            "msg_type": "interactive",
+            "card": {
+                "header": {
+                    "title": {"tag": "plain_text", "content": title},
+                    "template": severity.feishu_template(),
+                },
+                "elements": [{
+                    "tag": "div",
+                    "text": {"tag": "plain_text", "content": body},
+                }],
+            },
+        });
+        if let Some(secret) = &self.secret {
+            let timestamp = Utc::now().timestamp();
+            payload["timestamp"] = serde_json::Value::String(timestamp.to_string());
+            payload["sign"] = serde_json::Value::String(feishu_sign(secret, timestamp));
+        }
+
+        let response = self
+            .client
+            .post(&self.webhook_url)
+            .json(&payload)
+            .send()
+            .await?;
+        check_api_response(response, "code", "msg", 0).await
+    }
+}
+
+fn feishu_sign(secret: &str, timestamp: i64) -> String {
+    hmac_sha256_base64(format!("{}\n{}", timestamp, secret).as_bytes(), b"")
+}
+
+impl Notifier for FeishuNotifier {
+    fn notify<'a>(
+        &'a self,
+        report: &'a PowerReport,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
+
+            debug!("Sending Feishu notification");
+            self.send_card(
+                &message.title,
+                &message.body,
+                Severity::of(event, report.alert.as_ref()),
+            )
+            .await?;
+            debug!("Feishu notification sent successfully");
+            Ok(())
+        })
+    }
+
+    fn notify_error<'a>(
+        &'a self,
+        error_msg: &'a str,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            let message = self.templates.render_error(error_msg, event);
+
+            debug!("Sending Feishu error notification");
+            self.send_card(&message.title, &message.body, Severity::of(event, None))
+                .await?;
+            debug!("Feishu error notification sent successfully");
+            Ok(())
+        })
+    }
+}
+
+/// ServerChan title limit, in characters.
+const SERVERCHAN_TITLE_MAX_CHARS: usize = 32;
+
+pub struct ServerChanNotifier {
+    client: reqwest::Client,
+    send_url: String,
+    templates: MessageTemplates,
+}
+
+impl ServerChanNotifier {
+    pub fn new(send_url: String, templates: MessageTemplates) -> Self {
+        Self {
+            client: create_http_client(),
+            send_url,
+            templates,
+        }
+    }
+
+    /// The API URL of a SendKey: ServerChan³ keys ("sctp<uid>t...") have a
+    /// server per user, Turbo keys ("SCT...") share one.
+    fn send_url(sendkey: &str) -> Option<String> {
+        if sendkey.is_empty() {
+            return None;
+        }
+        let uid = sendkey
+            .strip_prefix("sctp")
+            .and_then(|rest| rest.split_once('t'))
+            .map(|(uid, _)| uid)
+            .filter(|uid| !uid.is_empty() && uid.chars().all(|c| c.is_ascii_digit()));
+        Some(match uid {
+            Some(uid) => format!("https://{}.push.ft07.com/send/{}.send", uid, sendkey),
+            None => format!("https://sctapi.ftqq.com/{}.send", sendkey),
+        })
+    }
+
+    async fn send_message(&self, title: &str, body: &str) -> Result<(), Box<dyn Error>> {
+        let title: String = title.chars().take(SERVERCHAN_TITLE_MAX_CHARS).collect();
+        // desp is Markdown, where a single line break does not start a new line
+        let desp = body.replace('\n', "\n\n");
+        let params = [("title", title.as_str()), ("desp", desp.as_str())];
+
+        let response = self
+            .client
+            .post(&self.send_url)
+            .form(&params)
+            .send()
+            .await?;
+        check_api_response(response, "code", "message", 0).await
+    }
+}
+
+impl Notifier for ServerChanNotifier {
+    fn notify<'a>(
+        &'a self,
+        report: &'a PowerReport,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
+
+            debug!("Sending ServerChan notification");
+            self.send_message(&message.title, &message.body).await?;
+            debug!("ServerChan notification sent successfully");
+            Ok(())
+        })
+    }
+
+    fn notify_error<'a>(
+        &'a self,
+        error_msg: &'a str,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            let message = self.templates.render_error(error_msg, event);
+
+            debug!("Sending ServerChan error notification");
+            self.send_message(&message.title, &message.body).await?;
+            debug!("ServerChan error notification sent successfully");
+            Ok(())
+        })
+    }
+}
+
+pub struct PushPlusNotifier {
+    client: reqwest::Client,
+    token: String,
+    topic: Option<String>,
+    templates: MessageTemplates,
+}
+
+impl PushPlusNotifier {
+    pub fn new(token: String, topic: Option<String>, templates: MessageTemplates) -> Self {
+        Self {
+            client: create_http_client(),
+            token,
+            topic,
+            templates,
+        }
+    }
+
+    async fn send_message(&self, title: &str, body: &str) -> Result<(), Box<dyn Error>> {
+        let mut payload = serde_json::json!({
+            "token": self.token,
+            "title": title,
+            "content": body,
+            "template": "txt",
+        });
+        if let Some(topic) = &self.topic {
+            payload["topic"] = serde_json::Value::String(topic.clone());
+        }
+
+        let response = self
+            .client
+            .post("https://www.pushplus.plus/send")
+            .json(&payload)
+            .send()
+            .await?;
+        check_api_response(response, "code", "msg", 200).await
+    }
+}
+
+impl Notifier for PushPlusNotifier {
+    fn notify<'a>(
+        &'a self,
+        report: &'a PowerReport,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
+
+            debug!("Sending PushPlus notification");
+            self.send_message(&message.title, &message.body).await?;
+            debug!("PushPlus notification sent successfully");
+            Ok(())
+        })
+    }
+
+    fn notify_error<'a>(
+        &'a self,
+        error_msg: &'a str,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            let message = self.templates.render_error(error_msg, event);
+
+            debug!("Sending PushPlus error notification");
+            self.send_message(&message.title, &message.body).await?;
+            debug!("PushPlus error notification sent successfully");
+            Ok(())
+        })
+    }
+}
+
+pub struct BarkNotifier {
+    client: reqwest::Client,
+    push_url: String,
+    device_key: String,
+    group: Option<String>,
+    sound: Option<String>,
+    default_level: BarkLevel,
+    quiet_hours: Option<QuietHours>,
+    templates: MessageTemplates,
+}
+
+impl BarkNotifier {
+    pub fn new(config: &NotifyConfig, templates: MessageTemplates) -> Self {
+        Self {
+            client: create_http_client(),
+            push_url: format!("{}/push", config.bark_server_url.trim_end_matches('/')),
+            device_key: config.bark_device_key.clone(),
+            group: optional_string(&config.bark_group),
+            sound: optional_string(&config.bark_sound),
+            default_level: config.bark_level,
+            quiet_hours: None,
+            templates,
+        }
+    }
+
+    pub fn with_quiet_hours(mut self, quiet_hours: Option<QuietHours>) -> Self {
+        self.quiet_hours = quiet_hours;
+        self
+    }
+
+    /// Lowers the level to the quiet hours one while they downgrade.
+    fn quiet_level(&self, level: BarkLevel, critical: bool) -> BarkLevel {
+        match &self.quiet_hours {
+            Some(quiet) if quiet.downgrades(Local::now().time(), critical) => {
+                level.min(quiet.bark_level)
+            }
+            _ => level,
+        }
+    }
+
+    async fn send_message(
+        &self,
+        title: &str,
+        body: &str,
+        level: BarkLevel,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut payload = serde_json::json!({
+            "device_key": self.device_key,
+            "title": title,
+            "body": body,
+            "level": level,
+        });
+        if let Some(group) = &self.group {
+            payload["group"] = serde_json::Value::String(group.clone());
+        }
+        if let Some(sound) = &self.sound {
+            payload["sound"] = serde_json::Value::String(sound.clone());
+        }
+
+        let response = self
+            .client
+            .post(&self.push_url)
+            .json(&payload)
+            .send()
+            .await?;
+        check_api_response(response, "code", "message", 200).await
+    }
+}
+
+impl Notifier for BarkNotifier {
+    fn notify<'a>(
+        &'a self,
+        report: &'a PowerReport,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
+            let level = match event {
+                // follows the alert level, critical without one
+                NotificationEvent::LowBalance => report
+                    .alert
+                    .as_ref()
+                    .map_or(BarkLevel::Critical, |alert| alert.level.bark_level()),
+                NotificationEvent::DepletionForecast => {
+                    self.default_level.max(BarkLevel::TimeSensitive)
+                }
+                _ => self.default_level,
+            };
+            let level = self.quiet_level(level, report.is_critical());
+
+            debug!("Sending Bark notification");
+            self.send_message(&message.title, &message.body, level)
+                .await?;
+            debug!("Bark notification sent successfully");
+            Ok(())
+        })
+    }
+
+    fn notify_error<'a>(
+        &'a self,
+        error_msg: &'a str,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            let message = self.templates.render_error(error_msg, event);
+
+            debug!("Sending Bark error notification");
+            let level = self.quiet_level(self.default_level, false);
+            self.send_message(&message.title, &message.body, level)
+                .await?;
+            debug!("Bark error notification sent successfully");
+            Ok(())
+        })
+    }
+}
+
+pub struct GotifyNotifier {
+    client: reqwest::Client,
+    message_url: String,
+    app_token: String,
+    default_priority: u8,
+    quiet_hours: Option<QuietHours>,
+    templates: MessageTemplates,
+}
+
+impl GotifyNotifier {
+    pub fn new(
+        server_url: &str,
+        app_token: String,
+        default_priority: u8,
+        templates: MessageTemplates,
+    ) -> Self {
+        Self {
+            client: create_http_client(),
+            message_url: format!("{}/message", server_url.trim_end_matches('/')),
+            app_token,
+            default_priority,
+            quiet_hours: None,
+            templates,
+        }
+    }
+
+    pub fn with_quiet_hours(mut self, quiet_hours: Option<QuietHours>) -> Self {
+        self.quiet_hours = quiet_hours;
+        self
+    }
+
+    /// Lowers the priority to the quiet hours one while they downgrade.
+    fn quiet_priority(&self, priority: u8, critical: bool) -> u8 {
+        match &self.quiet_hours {
+            Some(quiet) if quiet.downgrades(Local::now().time(), critical) => {
+                priority.min(quiet.gotify_priority)
+            }
+            _ => priority,
+        }
+    }
+
+    /// Priority of an event: the alert level for low balance, at least high
+    /// for a depletion forecast and the configured default otherwise.
+    fn priority(&self, event: NotificationEvent, alert: Option<&LowBalanceAlert>) -> u8 {
+        match event {
+            NotificationEvent::LowBalance => {
+                alert.map_or(10, |alert| alert.level.gotify_priority())
+            }
+            NotificationEvent::DepletionForecast => self.default_priority.max(7),
+            NotificationEvent::Heartbeat
+            | NotificationEvent::Recharge
+            | NotificationEvent::LoginFailure
+            | NotificationEvent::ConsecutiveFetchFailures => self.default_priority,
+        }
+    }
+
+    async fn send_message(
+        &self,
+        title: &str,
+        message: &str,
+        priority: u8,
+    ) -> Result<(), Box<dyn Error>> {
+        let payload = serde_json::json!({
+            "title": title,
+            "message": message,
+            "priority": priority.min(10),
+            "extras": {"client::display": {"contentType": "text/plain"}},
+        });
+
+        self.client
+            .post(&self.message_url)
+            .header("X-Gotify-Key", &self.app_token)
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+impl Notifier for GotifyNotifier {
+    fn notify<'a>(
+        &'a self,
+        report: &'a PowerReport,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
+
+            debug!("Sending Gotify notification");
+            self.send_message(
+                &message.title,
+                &message.body,
+                self.quiet_priority(
+                    self.priority(event, report.alert.as_ref()),
+                    report.is_critical(),
+                ),
+            )
+            .await?;
+            debug!("Gotify notification sent successfully");
+            Ok(())
+        })
+    }
+
+    fn notify_error<'a>(
+        &'a self,
+        error_msg: &'a str,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            let message = self.templates.render_error(error_msg, event);
+
+            debug!("Sending Gotify error notification");
+            let priority = self.quiet_priority(self.priority(event, None), false);
+            self.send_message(&message.title, &message.body, priority)
+                .await?;
+            debug!("Gotify error notification sent successfully");
+            Ok(())
+        })
+    }
+}
+
+/// Forwards notifications to an Apprise API server, which delivers them to
+/// any of the services Apprise supports.
+pub struct AppriseNotifier {
+    client: reqwest::Client,
+    notify_url: String,
+    /// Apprise URLs to notify, sent with every request
+    urls: Vec<String>,
+    tag: Option<String>,
+    templates: MessageTemplates,
+}
+
+impl AppriseNotifier {
+    pub fn new(config: &NotifyConfig, templates: MessageTemplates) -> Self {
+        let api_url = config.apprise_api_url.trim_end_matches('/');
+        // Stateless with URLs, else the URLs stored on the server under the key
+        let notify_url = if config.apprise_urls.is_empty() {
+            format!("{}/notify/{}", api_url, config.apprise_config_key)
+        } else {
+            format!("{}/notify/", api_url)
+        };
+        Self {
+            client: create_http_client(),
+            notify_url,
+            urls: config.apprise_urls.clone(),
+            tag: optional_string(&config.apprise_tag),
+            templates,
+        }
+    }
+
+    async fn send_message(
+        &self,
+        title: &str,
+        body: &str,
+        severity: Severity,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut payload = serde_json::json!({
+            "title": title,
+            "body": body,
+            "type": severity.apprise_type(),
+            "format": "text",
+        });
+        if !self.urls.is_empty() {
+            payload["urls"] = serde_json::Value::String(self.urls.join(","));
+        }
+        if let Some(tag) = &self.tag {
+            payload["tag"] = serde_json::Value::String(tag.clone());
+        }
+
+        self.client
+            .post(&self.notify_url)
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+impl Notifier for AppriseNotifier {
+    fn notify<'a>(
+        &'a self,
+        report: &'a PowerReport,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if event.is_error() {
+                return Ok(()); // These events use notify_error instead
+            }
+            let message = self.templates.render(report, event);
+
+            debug!("Sending Apprise notification");
+            self.send_message(
+                &message.title,
+                &message.body,
+                Severity::of(event, report.alert.as_ref()),
+            )
+            .await?;
+            debug!("Apprise notification sent successfully");
+            Ok(())
+        })
+    }
+
+    fn notify_error<'a>(
+        &'a self,
+        error_msg: &'a str,
+        event: NotificationEvent,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send + 'a>> {
+        Box::pin(async move {
+            if !event.is_error() {
+                return Ok(()); // These events use notify instead
+            }
+            let message = self.templates.render_error(error_msg, event);
+
+            debug!("Sending Apprise error notification");
+            self.send_message(&message.title, &message.body, Severity::of(event, None))
+                .await?;
+            debug!("Apprise error notification sent successfully");
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ChannelConfig, QuietMode};
+    use axum::Router;
+    use axum::body::Bytes;
+    use axum::http::{HeaderMap, Method, StatusCode, Uri};
+    use chrono::TimeZone;
+    use std::sync::atomic::{AtomicU16, Ordering};
+
+    /// A request received by the mock server.
+    struct Request {
+        method: Method,
+        path: String,
+        headers: HeaderMap,
+        raw_body: Bytes,
+        body: serde_json::Value,
+    }
+
+    type Requests = Arc<Mutex<Vec<Request>>>;
+
+    /// How the mock server answers, can be changed while it runs.
+    #[derive(Default)]
+    struct Responses {
+        /// Status code to answer with, 200 when 0
+        status: AtomicU16,
+        /// Time to wait before answering
+        delay: Mutex<Duration>,
+    }
+
+    /// Starts an HTTP server on a free local port that records every request
+    /// and answers it with 200. Returns its base URL.
+    async fn mock_server() -> (String, Requests) {
+        mock_server_with(Arc::default()).await
+    }
+
+    async fn mock_server_with(responses: Arc<Responses>) -> (String, Requests) {
+        let requests = Requests::default();
+        let recorded = requests.clone();
+        let app = Router::new().fallback(
+            move |method: Method, uri: Uri, headers: HeaderMap, body: Bytes| {
+                let recorded = recorded.clone();
+                let responses = responses.clone();
+                async move {
+                    recorded.lock().unwrap().push(Request {
+                        method,
+                        path: uri.path().to_string(),
+                        headers,
+                        body: serde_json::from_slice(&body).unwrap_or_default(),
+                        raw_body: body,
+                    });
+                    let delay = *responses.delay.lock().unwrap();
+                    tokio::time::sleep(delay).await;
+                    let status = match responses.status.load(Ordering::SeqCst) {
+                        0 => StatusCode::OK,
+                        code => StatusCode::from_u16(code).unwrap(),
+                    };
+                    (status, "{}")
+                }
+            },
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, requests)
+    }
+
+    fn report(level: Option<AlertLevel>) -> PowerReport {
+        let info: PowerInfo = serde_json::from_value(serde_json::json!({
+            "retcode": 0,
+            "msg": "",
+            "sydl": "26.9",
+            "syje": "4.4",
+            "dffjbh": "m1",
+            "roomName": "Room 101",
+            "roomId": "r1",
+            "buiId": "b1",
+            "areaid": "a1",
+            "fjh": "101",
+        }))
+        .unwrap();
+        let alert = level.map(|level| {
+            LowBalanceAlert::from(&ThresholdTier {
+                threshold: 5.0,
+                level,
+                cooldown_minutes: None,
+                pushover_priority: None,
+                ntfy_priority: None,
+                channels: Vec::new(),
+            })
+        });
+        PowerReport {
+            info,
+            forecast: None,
+            recharge_amount: None,
+            alert,
+        }
+    }
+
+    /// A migrated in-memory SQLite database.
+    async fn memory_store(account: &str) -> StateStore {
+        let db = DbService::new("sqlite::memory:".to_string()).await.unwrap();
+        db.init().await.unwrap();
+        StateStore {
+            db,
+            account: account.to_string(),
+        }
+    }
+
+    fn channel(name: &str, notify_type: NotifyType, config: &NotifyConfig) -> ChannelNotifier {
+        ChannelNotifier {
+            name: name.to_string(),
+            notify_type: notify_type.clone(),
+            notifier: create_single_notifier(config, notify_type).unwrap(),
+            quiet_hours: None,
+        }
+    }
+
+    fn delivery(
+        notifiers: Vec<ChannelNotifier>,
+        store: Option<StateStore>,
+        timeout: Duration,
+    ) -> Arc<Delivery> {
+        Arc::new(Delivery {
+            notifiers,
+            store,
+            timeout,
+            outbox_max_age_minutes: 60,
+            in_flight: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Current value of `upm_notifier_send_failures_total` for a notify type.
+    fn notifier_failures(notify_type: &str) -> f64 {
+        let series = format!(
+            "upm_notifier_send_failures_total{{notify_type=\"{}\"}} ",
+            notify_type
+        );
+        metrics()
+            .gather()
+            .lines()
+            .find_map(|line| line.strip_prefix(&series))
+            .map_or(0.0, |value| value.parse().unwrap())
+    }
+
+    fn console_channel(name: &str) -> ChannelConfig {
+        ChannelConfig {
+            name: name.to_string(),
+            notify_type: NotifyType::Console,
+            settings: serde_json::Map::new(),
+        }
+    }
+
+    /// A manager whose dispatches are collected instead of delivered.
+    async fn capturing_manager(
+        config: NotifyConfig,
+    ) -> (NotificationManager, mpsc::UnboundedReceiver<Dispatch>) {
+        let mut manager = NotificationManager::new(config, None).await.unwrap();
+        let (dispatcher, receiver) = mpsc::unbounded_channel();
+        manager.dispatcher = dispatcher;
+        (manager, receiver)
+    }
+
+    fn dispatched(receiver: &mut mpsc::UnboundedReceiver<Dispatch>) -> Vec<Dispatch> {
+        std::iter::from_fn(|| receiver.try_recv().ok()).collect()
+    }
+
+    /// The low balance alerts among `dispatches`, as (threshold, channels).
+    fn alerts(dispatches: &[Dispatch]) -> Vec<(f64, Vec<usize>)> {
+        dispatches
+            .iter()
+            .filter(|dispatch| dispatch.event == NotificationEvent::LowBalance)
+            .map(|dispatch| {
+                let NotificationPayload::Report(report) = &dispatch.payload else {
+                    panic!("low balance dispatch without a report");
+                };
+                let channels = dispatch.channels.iter().map(|&(index, _)| index).collect();
+                (report.alert.as_ref().unwrap().threshold, channels)
+            })
+            .collect()
+    }
+
+    fn balance(money: f64) -> PowerReport {
+        let mut report = report(None);
+        report.info.remaining_money = money;
+        report
+    }
+
+    fn tier(threshold: f64, level: AlertLevel) -> ThresholdTier {
+        ThresholdTier {
+            threshold,
+            level,
+            cooldown_minutes: None,
+            pushover_priority: None,
+            ntfy_priority: None,
+            channels: Vec::new(),
+        }
+    }
+
+    /// Tiers at 20, 10 (one hour cooldown) and 3 (only to "bob", with a
+    /// lower Pushover priority), sent to the console channels alice, bob and
+    /// admin.
+    fn tiered_config() -> NotifyConfig {
+        NotifyConfig {
+            enabled: true,
+            cooldown_minutes: 1440,
+            heartbeat_enabled: false,
+            depletion_alert_enabled: false,
+            threshold_tiers: vec![
+                tier(20.0, AlertLevel::Info),
+                ThresholdTier {
+                    cooldown_minutes: Some(60),
+                    ..tier(10.0, AlertLevel::Warning)
+                },
+                ThresholdTier {
+                    pushover_priority: Some(1),
+                    channels: vec!["bob".to_string()],
+                    ..tier(3.0, AlertLevel::Critical)
+                },
+            ],
+            channels: vec![
+                console_channel("alice"),
+                console_channel("bob"),
+                console_channel("admin"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn gotify(url: &str) -> GotifyNotifier {
+        let config = NotifyConfig {
+            gotify_priority: 4,
+            ..Default::default()
+        };
+        GotifyNotifier::new(
+            url,
+            "app-token".to_string(),
+            config.gotify_priority,
+            MessageTemplates::new(&config, &NotifyType::Gotify),
+        )
+    }
+
+    fn apprise(config: NotifyConfig) -> AppriseNotifier {
+        let templates = MessageTemplates::new(&config, &NotifyType::Apprise);
+        AppriseNotifier::new(&config, templates)
+    }
+
+    #[tokio::test]
+    async fn gotify_sends_the_app_token() {
+        let (url, requests) = mock_server().await;
+        gotify(&url)
+            .notify(&report(None), NotificationEvent::Heartbeat)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/message");
+        assert_eq!(requests[0].headers["x-gotify-key"], "app-token");
+        assert_eq!(
+            requests[0].body["extras"]["client::display"]["contentType"],
+            "text/plain"
+        );
+    }
+
+    #[tokio::test]
+    async fn gotify_maps_events_to_priorities() {
+        let (url, requests) = mock_server().await;
+        let notifier = gotify(&url);
+        for level in [AlertLevel::Info, AlertLevel::Warning, AlertLevel::Critical] {
+            notifier
+                .notify(&report(Some(level)), NotificationEvent::LowBalance)
+                .await
+                .unwrap();
+        }
+        for event in [
+            NotificationEvent::DepletionForecast,
+            NotificationEvent::Heartbeat,
+            NotificationEvent::Recharge,
+        ] {
+            notifier.notify(&report(None), event).await.unwrap();
+        }
+        notifier
+            .notify_error("login failed", NotificationEvent::LoginFailure)
+            .await
+            .unwrap();
+
+        let priorities: Vec<_> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| request.body["priority"].as_u64().unwrap())
+            .collect();
+        assert_eq!(priorities, [5, 7, 10, 7, 4, 4, 4]);
+    }
+
+    /// Default quiet hours from 23:00 to 07:00.
+    fn night_quiet_hours() -> QuietHours {
+        serde_json::from_value(serde_json::json!({ "start": "23:00:00", "end": "07:00:00" }))
+            .unwrap()
+    }
+
+    #[test]
+    fn quiet_hours_defer_the_legacy_threshold_alert() {
+        let config = NotifyConfig {
+            threshold: 10.0,
+            quiet_hours: Some(night_quiet_hours()),
+            ..Default::default()
+        };
+        let mut report = report(None);
+        report.alert = Some(LowBalanceAlert::from(&config.low_balance_tiers()[0]));
+        assert!(report.is_critical());
+        let payload = NotificationPayload::Report(Box::new(report));
+        let channel = ChannelNotifier {
+            name: "console".to_string(),
+            notify_type: NotifyType::Console,
+            notifier: Box::new(ConsoleNotifier::new(MessageTemplates::new(
+                &config,
+                &NotifyType::Console,
+            ))),
+            quiet_hours: config.quiet_hours.clone(),
+        };
+
+        let local = |hour| {
+            Local
+                .with_ymd_and_hms(2025, 1, 15, hour, 0, 0)
+                .earliest()
+                .unwrap()
+        };
+        assert_eq!(channel.deferred_until(&payload, local(3)), Some(local(7)));
+        assert_eq!(channel.deferred_until(&payload, local(12)), None);
+    }
+
+    #[tokio::test]
+    async fn quiet_hours_downgrade_the_legacy_threshold_alert() {
+        let (url, requests) = mock_server().await;
+        let now = Local::now().time();
+        let quiet = QuietHours {
+            start: now - chrono::Duration::hours(1),
+            end: now + chrono::Duration::hours(1),
+            mode: QuietMode::Downgrade,
+            ..night_quiet_hours()
+        };
+        let notifier = gotify(&url).with_quiet_hours(Some(quiet));
+        let config = NotifyConfig::default();
+        let mut report = report(None);
+        report.alert = Some(LowBalanceAlert::from(&config.low_balance_tiers()[0]));
+        notifier
+            .notify(&report, NotificationEvent::LowBalance)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].body["priority"], 3);
+    }
+
+    #[tokio::test]
+    async fn apprise_stateless_sends_the_urls() {
+        let (url, requests) = mock_server().await;
+        let notifier = apprise(NotifyConfig {
+            apprise_api_url: format!("{}/", url),
+            apprise_urls: vec!["json://a.example".to_string(), "tgram://b/c".to_string()],
+            ..Default::default()
+        });
+        notifier
+            .notify(
+                &report(Some(AlertLevel::Warning)),
+                NotificationEvent::LowBalance,
+            )
+            .await
+            .unwrap();
+        notifier
+            .notify_error("login failed", NotificationEvent::LoginFailure)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        for request in requests.iter() {
+            assert_eq!(request.path, "/notify/");
+            assert_eq!(request.body["urls"], "json://a.example,tgram://b/c");
+            assert_eq!(request.body["format"], "text");
+            assert!(request.body.get("tag").is_none());
+        }
+        assert_eq!(requests[0].body["type"], "warning");
+        assert_eq!(requests[1].body["type"], "failure");
+    }
+
+    #[tokio::test]
+    async fn apprise_keyed_uses_the_stored_config() {
+        let (url, requests) = mock_server().await;
+        let notifier = apprise(NotifyConfig {
+            apprise_api_url: url,
+            apprise_config_key: "home".to_string(),
+            apprise_tag: "admins".to_string(),
+            ..Default::default()
+        });
+        notifier
+            .notify(&report(None), NotificationEvent::Recharge)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/notify/home");
+        assert!(requests[0].body.get("urls").is_none());
+        assert_eq!(requests[0].body["tag"], "admins");
+        assert_eq!(requests[0].body["type"], "success");
+    }
+
+    #[tokio::test]
+    async fn outbox_keeps_failed_notifications_until_redelivered() {
+        let responses = Arc::new(Responses::default());
+        responses.status.store(503, Ordering::SeqCst);
+        let (url, requests) = mock_server_with(responses.clone()).await;
+        let store = memory_store("acc").await;
+        let config = NotifyConfig {
+            gotify_server_url: url,
+            gotify_app_token: "app-token".to_string(),
+            ..Default::default()
+        };
+        // the only test failing a Gotify channel, so the counter is exact
+        let failures = notifier_failures("gotify");
+        let delivery = delivery(
+            vec![channel("gotify", NotifyType::Gotify, &config)],
+            Some(store.clone()),
+            Duration::from_secs(30),
+        );
+
+        let payload = NotificationPayload::Report(Box::new(report(None)));
+        assert!(
+            !delivery
+                .deliver(NotificationEvent::Heartbeat, &payload, &[0])
+                .await
+        );
+        assert_eq!(requests.lock().unwrap().len(), DELIVERY_ATTEMPTS);
+        assert_eq!(notifier_failures("gotify"), failures + 1.0);
+        let later = Utc::now() + chrono::Duration::hours(1);
+        let entries = store.db.due_outbox("acc", later).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].channel, "gotify");
+        assert_eq!(entries[0].error_class.as_deref(), Some("http_503"));
+
+        let worker = OutboxWorker {
+            store: store.clone(),
+            delivery,
+            tiers: Vec::new(),
+        };
+        // still down: rescheduled and counted again
+        worker.redeliver(entries[0].clone()).await;
+        assert_eq!(notifier_failures("gotify"), failures + 2.0);
+        let entries = store.db.due_outbox("acc", later).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].attempts, 1);
+
+        responses.status.store(0, Ordering::SeqCst);
+        worker.redeliver(entries[0].clone()).await;
+        assert!(store.db.due_outbox("acc", later).await.unwrap().is_empty());
+        assert_eq!(requests.lock().unwrap().len(), DELIVERY_ATTEMPTS + 2);
+        assert_eq!(notifier_failures("gotify"), failures + 2.0);
+    }
+
+    #[tokio::test]
+    async fn fetch_failure_gauge_follows_the_restored_counter() {
+        let store = memory_store("restored").await;
+        store
+            .db
+            .save_notification_state(&NotificationState {
+                account: "restored".to_string(),
+                low_balance_threshold: None,
+                last_low_balance_notify_at: None,
+                last_heartbeat_date: None,
+                consecutive_fetch_failures: 4,
+                last_fetch_failure_notify_at: None,
+                last_depletion_notify_at: None,
+            })
+            .await
+            .unwrap();
+        let gauge = || {
+            metrics()
+                .gather()
+                .lines()
+                .find_map(|line| {
+                    line.strip_prefix("upm_consecutive_fetch_failures{account=\"restored\"} ")
+                })
+                .map(|value| value.parse::<f64>().unwrap())
+        };
+        let config = NotifyConfig {
+            enabled: true,
+            notify_type: NotifyType::Console,
+            ..Default::default()
+        };
+
+        let mut manager = NotificationManager::new(config, Some(store)).await.unwrap();
+        assert_eq!(gauge(), Some(4.0));
+        manager.record_fetch_failure().await;
+        assert_eq!(gauge(), Some(5.0));
+        manager.reset_fetch_failures().await;
+        assert_eq!(gauge(), Some(0.0));
+    }
+
+    #[test]
+    fn hmac_matches_the_rfc_4231_vector() {
+        assert_eq!(
+            hmac_sha256_hex(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn webhook_signature_covers_the_timestamp() {
+        let body = r#"{"event":"heartbeat"}"#;
+        assert_eq!(
+            webhook_signature("secret", 1_700_000_000, body),
+            "sha256=2f1b6a3736f4ab17edeeea3a8e753d01f4c30713d00ebf8b1d566fd4bf7ed455"
+        );
+        assert_ne!(
+            webhook_signature("secret", 1_700_000_001, body),
+            webhook_signature("secret", 1_700_000_000, body)
+        );
+    }
+
+    #[tokio::test]
+    async fn webhook_signs_the_sent_body() {
+        let (url, requests) = mock_server().await;
+        let notifier = WebhookNotifier::new(
+            url,
+            reqwest::Method::POST,
+            reqwest::header::HeaderMap::new(),
+            None,
+            Some("secret".to_string()),
+        );
+        notifier
+            .notify(&report(None), NotificationEvent::Heartbeat)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        let request = &requests[0];
+        let timestamp: i64 = request.headers["x-signature-timestamp"]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((Utc::now().timestamp() - timestamp).abs() < 60);
+        let body = std::str::from_utf8(&request.raw_body).unwrap();
+        assert_eq!(
+            request.headers["x-signature"],
+            webhook_signature("secret", timestamp, body).as_str()
+        );
+        assert_eq!(request.method, Method::POST);
+        assert_eq!(request.headers["content-type"], "application/json");
+        assert_eq!(request.headers["x-event-type"], "heartbeat");
+        assert_eq!(request.body["version"], WEBHOOK_PAYLOAD_VERSION);
+        assert_eq!(request.body["event"], "heartbeat");
+        assert_eq!(request.body["report"]["remaining_money"], 4.4);
+        assert!(request.body["error"].is_null());
+    }
+
+    #[tokio::test]
+    async fn webhook_uses_the_configured_method_headers_and_template() {
+        let (url, requests) = mock_server().await;
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Authorization", "Bearer abc".parse().unwrap());
+        headers.insert("Content-Type", "text/plain".parse().unwrap());
+        let template = WebhookTemplate::new(
+            r#"{"text": {{ (event ~ ": " ~ (error or report.remaining_money|fixed))|tojson }}}"#,
+        )
+        .unwrap();
+        let notifier = WebhookNotifier::new(
+            format!("{}/hook", url),
+            reqwest::Method::PUT,
+            headers,
+            Some(template),
+            None,
+        );
+        notifier
+            .notify(&report(None), NotificationEvent::Recharge)
+            .await
+            .unwrap();
+        notifier
+            .notify_error("login failed", NotificationEvent::LoginFailure)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        for request in requests.iter() {
+            assert_eq!(request.method, Method::PUT);
+            assert_eq!(request.path, "/hook");
+            assert_eq!(request.headers["authorization"], "Bearer abc");
+            assert_eq!(request.headers["content-type"], "text/plain");
+            assert!(request.headers.get("x-signature").is_none());
+        }
+        assert_eq!(
+            requests[0].body,
+            serde_json::json!({ "text": "recharge: 4.40" })
+        );
+        assert_eq!(
+            requests[1].body,
+            serde_json::json!({ "text": "login_failure: login failed" })
+        );
+    }
+
+    #[test]
+    fn dingtalk_signs_the_url() {
+        let config = NotifyConfig::default();
+        let notifier = DingTalkNotifier::new(
+            reqwest::Url::parse("https://oapi.dingtalk.com/robot/send?access_token=abc").unwrap(),
+            Some("SECabc".to_string()),
+            MessageTemplates::new(&config, &NotifyType::DingTalk),
+        );
+        assert_eq!(
+            notifier.signed_url(1_700_000_000_000).as_str(),
+            "https://oapi.dingtalk.com/robot/send?access_token=abc\
+             &timestamp=1700000000000\
+             &sign=jcUpW0QmtKduN03n4JqQ0PBosVjqnM8gU7fIIvsDmCM%3D"
+        );
+
+        let unsigned = DingTalkNotifier::new(
+            reqwest::Url::parse("https://oapi.dingtalk.com/robot/send?access_token=abc").unwrap(),
+            None,
+            MessageTemplates::new(&config, &NotifyType::DingTalk),
+        );
+        assert_eq!(
+            unsigned.signed_url(1_700_000_000_000).as_str(),
+            "https://oapi.dingtalk.com/robot/send?access_token=abc"
+        );
+    }
+
+    #[test]
+    fn feishu_signs_the_timestamp() {
+        assert_eq!(
+            feishu_sign("SECabc", 1_700_000_000),
+            "XprR1de+0SSBnwWyU/4k6x2TL+Q2SJlM5NNEdAv7MWg="
+        );
+    }
+
+    #[test]
+    fn dingtalk_needs_a_valid_webhook_url() {
+        let config = |url: &str| NotifyConfig {
+            dingtalk_webhook_url: url.to_string(),
+            ..Default::default()
+        };
+        assert!(create_single_notifier(&config(""), NotifyType::DingTalk).is_none());
+        assert!(create_single_notifier(&config("not a url"), NotifyType::DingTalk).is_none());
+        assert!(
+            create_single_notifier(
+                &config("https://oapi.dingtalk.com/robot/send?access_token=abc"),
+                NotifyType::DingTalk
+            )
+            .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn tiers_fire_once_each_on_the_way_down() {
+        let (mut manager, mut receiver) = capturing_manager(tiered_config()).await;
+        for money in [25.0, 15.0, 14.0, 8.0, 7.0, 2.0, 1.0] {
+            manager.check_and_notify(&balance(money)).await;
+        }
+        let thresholds: Vec<_> = alerts(&dispatched(&mut receiver))
+            .into_iter()
+            .map(|(threshold, _)| threshold)
+            .collect();
+        assert_eq!(thresholds, [20.0, 10.0, 3.0]);
+    }
+
+    #[tokio::test]
+    async fn tiers_repeat_only_after_their_cooldown() {
+        let (mut manager, mut receiver) = capturing_manager(tiered_config()).await;
+        manager.check_and_notify(&balance(8.0)).await;
+        manager.check_and_notify(&balance(8.0)).await;
+        manager.check_and_notify(&balance(7.5)).await;
+        assert_eq!(alerts(&dispatched(&mut receiver)).len(), 1);
+
+        // the tier cooldown (60 minutes) applies, not the global one (a day)
+        manager.last_low_balance_notify_time = Some(Local::now() - chrono::Duration::minutes(61));
+        manager.check_and_notify(&balance(7.5)).await;
+        let alerts = alerts(&dispatched(&mut receiver));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].0, 10.0);
+    }
+
+    #[tokio::test]
+    async fn recharge_above_a_tier_rearms_it() {
+        let (mut manager, mut receiver) = capturing_manager(tiered_config()).await;
+        manager.check_and_notify(&balance(8.0)).await;
+        manager.check_and_notify(&balance(50.0)).await;
+        assert_eq!(manager.active_tier, None);
+        manager.check_and_notify(&balance(8.0)).await;
+        let thresholds: Vec<_> = alerts(&dispatched(&mut receiver))
+            .into_iter()
+            .map(|(threshold, _)| threshold)
+            .collect();
+        assert_eq!(thresholds, [10.0, 10.0]);
+    }
+
+    #[tokio::test]
+    async fn tiers_use_their_channels_and_priorities() {
+        let (mut manager, mut receiver) = capturing_manager(tiered_config()).await;
+        manager.check_and_notify(&balance(8.0)).await;
+        manager.check_and_notify(&balance(2.0)).await;
+        let dispatches = dispatched(&mut receiver);
+        // alice, bob and admin without tier channels, only bob for the 3 tier
+        assert_eq!(alerts(&dispatches), [(10.0, vec![0, 1, 2]), (3.0, vec![1])]);
+
+        let NotificationPayload::Report(report) = &dispatches[1].payload else {
+            panic!("low balance dispatch without a report");
+        };
+        let alert = report.alert.as_ref().unwrap();
+        assert_eq!(alert.level, AlertLevel::Critical);
+        assert_eq!(alert.pushover_priority, 1);
+        assert_eq!(alert.ntfy_priority, AlertLevel::Critical.ntfy_priority());
+    }
+
+    #[tokio::test]
+    async fn events_are_routed_by_tier_then_route() {
+        let event = NotificationEvent::LowBalance;
+        // (routes of the event, tier channels, expected indexes of alice, bob, admin)
+        let cases: [(Option<&[&str]>, &[&str], &[usize]); 7] = [
+            (None, &[], &[0, 1, 2]),
+            (Some(&["admin"]), &[], &[2]),
+            (Some(&[]), &[], &[]),
+            (None, &["bob"], &[1]),
+            (Some(&["admin"]), &["bob", "alice"], &[0, 1]),
+            (Some(&["ghost"]), &[], &[]),
+            (None, &["ghost"], &[]),
+        ];
+        for (route, tier_channels, expected) in cases {
+            let mut config = tiered_config();
+            if let Some(route) = route {
+                config.routes.insert(
+                    event.as_str().to_string(),
+                    route.iter().map(|c| c.to_string()).collect(),
+                );
+            }
+            let (manager, _) = capturing_manager(config).await;
+            let tier_channels: Vec<String> = tier_channels.iter().map(|c| c.to_string()).collect();
+            assert_eq!(
+                manager.routed_channels(event, &tier_channels),
+                expected,
+                "route {:?}, tier channels {:?}",
+                route,
+                tier_channels
+            );
+            // other events are not affected by the route
+            assert_eq!(
+                manager.routed_channels(NotificationEvent::Heartbeat, &[]),
+                [0, 1, 2]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_channel_does_not_hold_up_the_others() {
+        let slow_responses = Arc::new(Responses::default());
+        *slow_responses.delay.lock().unwrap() = Duration::from_secs(10);
+        let (slow_url, slow) = mock_server_with(slow_responses).await;
+        let (fast_url, fast) = mock_server().await;
+        let webhook = |name: &str, url: String| {
+            let mut settings = serde_json::Map::new();
+            settings.insert("webhook_url".to_string(), url.into());
+            ChannelConfig {
+                name: name.to_string(),
+                notify_type: NotifyType::Webhook,
+                settings,
+            }
+        };
+        let store = memory_store("slow").await;
+        let config = NotifyConfig {
+            channel_timeout_seconds: 2,
+            outbox_max_age_minutes: 60,
+            channels: vec![webhook("slow", slow_url), webhook("fast", fast_url)],
+            ..tiered_config()
+        };
+        let mut manager = NotificationManager::new(config, Some(store.clone()))
+            .await
+            .unwrap();
+
+        let started = std::time::Instant::now();
+        manager.check_and_notify(&balance(5.0)).await;
+        while fast.lock().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(slow.lock().unwrap().len(), 1);
+
+        let attempts = loop {
+            let attempts = store
+                .db
+                .notifications(Some("slow"), None, None, None, 10)
+                .await
+                .unwrap();
+            if attempts.len() == 2 {
+                break attempts;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        };
+        let attempt = |channel: &str| attempts.iter().find(|a| a.channel == channel).unwrap();
+        assert!(attempt("fast").success);
+        assert!(!attempt("slow").success);
+        assert_eq!(attempt("slow").error_class.as_deref(), Some("timeout"));
+        let queued = store
+            .db
+            .due_outbox("slow", Utc::now() + chrono::Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].channel, "slow");
+    }
+}
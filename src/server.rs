@@ -0,0 +1,164 @@
+use crate::config::ServerConfig;
+use crate::db::DbService;
+use crate::metrics::metrics;
+use crate::utils::{parse_end_time, parse_time};
+use axum::extract::{Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use tokio::net::TcpListener;
+use tracing::{debug, error, info};
+
+const DEFAULT_HISTORY_LIMIT: i64 = 1000;
+const MAX_HISTORY_LIMIT: i64 = 10_000;
+
+type ApiResult<T> = Result<Json<T>, (StatusCode, Json<serde_json::Value>)>;
+
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    room: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NotificationQuery {
+    account: Option<String>,
+    event: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    limit: Option<i64>,
+}
+
+/// Binds the HTTP server and serves it in the background.
+pub async fn spawn(config: &ServerConfig, db: DbService) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(&config.bind).await?;
+    info!("HTTP server listening on {}", listener.local_addr()?);
+
+    let app = router(db);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            error!("HTTP server stopped: {}", e);
+        }
+    });
+    Ok(())
+}
+
+fn router(db: DbService) -> Router {
+    Router::new()
+        .route("/api/latest", get(latest))
+        .route("/api/history", get(history))
+        .route("/api/rooms", get(rooms))
+        .route("/api/recharges", get(recharges))
+        .route("/api/notifications", get(notifications))
+        .route("/metrics", get(prometheus_metrics))
+        .with_state(db)
+}
+
+async fn latest(State(db): State<DbService>) -> ApiResult<Vec<crate::db::PowerRecord>> {
+    debug!("GET /api/latest");
+    db.latest_records().await.map(Json).map_err(internal_error)
+}
+
+async fn history(
+    State(db): State<DbService>,
+    Query(query): Query<HistoryQuery>,
+) -> ApiResult<Vec<crate::db::PowerRecord>> {
+    debug!("GET /api/history: {:?}", query);
+    let from = parse_time_param("from", query.from.as_deref(), parse_time)?;
+    let to = parse_time_param("to", query.to.as_deref(), parse_end_time)?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+        .clamp(1, MAX_HISTORY_LIMIT);
+    let room = query.room.as_deref().filter(|r| !r.is_empty());
+
+    db.history(room, from, to, limit)
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+async fn recharges(
+    State(db): State<DbService>,
+    Query(query): Query<HistoryQuery>,
+) -> ApiResult<Vec<crate::db::RechargeRecord>> {
+    debug!("GET /api/recharges: {:?}", query);
+    let from = parse_time_param("from", query.from.as_deref(), parse_time)?;
+    let to = parse_time_param("to", query.to.as_deref(), parse_end_time)?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+        .clamp(1, MAX_HISTORY_LIMIT);
+    let room = query.room.as_deref().filter(|r| !r.is_empty());
+
+    db.recharges(room, from, to, limit)
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+async fn notifications(
+    State(db): State<DbService>,
+    Query(query): Query<NotificationQuery>,
+) -> ApiResult<Vec<crate::db::NotificationRecord>> {
+    debug!("GET /api/notifications: {:?}", query);
+    let from = parse_time_param("from", query.from.as_deref(), parse_time)?;
+    let to = parse_time_param("to", query.to.as_deref(), parse_end_time)?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+        .clamp(1, MAX_HISTORY_LIMIT);
+    let account = query.account.as_deref().filter(|a| !a.is_empty());
+    let event = query.event.as_deref().filter(|e| !e.is_empty());
+
+    db.notifications(account, event, from, to, limit)
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+async fn rooms(State(db): State<DbService>) -> ApiResult<Vec<crate::db::RoomSummary>> {
+    debug!("GET /api/rooms");
+    db.rooms().await.map(Json).map_err(internal_error)
+}
+
+async fn prometheus_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics().gather(),
+    )
+}
+
+/// Accepts RFC 3339 timestamps, or `YYYY-MM-DD[ HH:MM:SS]` interpreted in local time.
+fn parse_time_param(
+    name: &str,
+    value: Option<&str>,
+    parse: fn(&str) -> Option<DateTime<Utc>>,
+) -> Result<Option<DateTime<Utc>>, (StatusCode, Json<serde_json::Value>)> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+
+    match parse(value) {
+        Some(time) => Ok(Some(time)),
+        None => Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": format!("invalid '{}' parameter: expected RFC 3339 or YYYY-MM-DD[ HH:MM:SS]", name)
+            })),
+        )),
+    }
+}
+
+fn internal_error(e: Box<dyn std::error::Error>) -> (StatusCode, Json<serde_json::Value>) {
+    error!("HTTP request failed: {}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({ "error": "internal server error" })),
+    )
+}
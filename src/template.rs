@@ -0,0 +1,568 @@
+use crate::config::{Locale, MessageTemplate, NotifyConfig, NotifyType};
+use crate::notify::{NotificationEvent, PowerReport};
+use chrono::Local;
+use minijinja::{Environment, Value, context};
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing::{error, warn};
+
+/// Built-in templates of one language, from `templates/<locale>/`.
+///
+/// `macros` holds the shared titles, labels and text fragments, available to
+/// every template through `{% import "macros" as m %}`. The bodies are picked
+/// per channel: console and Telegram send a single text, email a long form,
+/// Discord and Slack what is not already shown as embed fields and the other
+/// channels a short body under the title. `error_message` is the `error` text
+/// of error events.
+struct Catalog {
+    macros: &'static str,
+    title: &'static str,
+    generic: &'static str,
+    generic_error: &'static str,
+    console: &'static str,
+    console_error: &'static str,
+    telegram: &'static str,
+    telegram_error: &'static str,
+    email: &'static str,
+    email_error: &'static str,
+    embed: &'static str,
+    embed_error: &'static str,
+    error_message: &'static str,
+}
+
+macro_rules! catalog {
+    ($locale:literal) => {
+        Catalog {
+            macros: include_str!(concat!("../templates/", $locale, "/macros.j2")),
+            title: include_str!(concat!("../templates/", $locale, "/title.j2")),
+            generic: include_str!(concat!("../templates/", $locale, "/generic.j2")),
+            generic_error: include_str!(concat!("../templates/", $locale, "/generic_error.j2")),
+            console: include_str!(concat!("../templates/", $locale, "/console.j2")),
+            console_error: include_str!(concat!("../templates/", $locale, "/console_error.j2")),
+            telegram: include_str!(concat!("../templates/", $locale, "/telegram.j2")),
+            telegram_error: include_str!(concat!("../templates/", $locale, "/telegram_error.j2")),
+            email: include_str!(concat!("../templates/", $locale, "/email.j2")),
+            email_error: include_str!(concat!("../templates/", $locale, "/email_error.j2")),
+            embed: include_str!(concat!("../templates/", $locale, "/embed.j2")),
+            embed_error: include_str!(concat!("../templates/", $locale, "/embed_error.j2")),
+            error_message: include_str!(concat!("../templates/", $locale, "/error_message.j2")),
+        }
+    };
+}
+
+static EN: Catalog = catalog!("en");
+static ZH_CN: Catalog = catalog!("zh-CN");
+
+impl Catalog {
+    fn for_locale(locale: Locale) -> &'static Catalog {
+        match locale {
+            Locale::En => &EN,
+            Locale::ZhCn => &ZH_CN,
+        }
+    }
+}
+
+const PARTS: [&str; 2] = ["title", "body"];
+
+/// Title and body of a notification, ready to send.
+#[derive(Debug, Clone)]
+pub struct RenderedMessage {
+    pub title: String,
+    pub body: String,
+}
+
+/// Renders notification messages for one channel.
+///
+/// User templates from `notify.channel_templates.<channel>.<event>` take
+/// priority over `notify.templates.<event>`; anything missing, invalid or
+/// failing to render falls back to the built-in text of the channel in the
+/// configured `notify.locale`.
+pub struct MessageTemplates {
+    env: Environment<'static>,
+}
+
+#[derive(Serialize)]
+struct MessageContext<'a> {
+    event: &'static str,
+    time: String,
+    timestamp: String,
+    #[serde(flatten)]
+    power: Option<PowerContext<'a>>,
+    error: Option<&'a str>,
+}
+
+/// The fields of a power report as templates and the webhook payload see
+/// them.
+#[derive(Serialize)]
+pub(crate) struct PowerContext<'a> {
+    remaining_money: f64,
+    remaining_energy: f64,
+    room_display_name: &'a str,
+    room_id: &'a str,
+    meter_room_id: &'a str,
+    building_id: &'a str,
+    campus_id: &'a str,
+    room_number: &'a str,
+    recharge_amount: Option<f64>,
+    forecast: Option<ForecastContext>,
+    level: Option<&'static str>,
+    threshold: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct ForecastContext {
+    kwh_per_day: f64,
+    cny_per_day: f64,
+    days_until_empty: Option<f64>,
+    depletion_time: Option<String>,
+    window_days: f64,
+}
+
+impl<'a> PowerContext<'a> {
+    pub(crate) fn new(report: &'a PowerReport) -> Self {
+        let info = &report.info;
+        Self {
+            remaining_money: info.remaining_money,
+            remaining_energy: info.remaining_energy,
+            room_display_name: &info.room_display_name,
+            room_id: &info.room_id,
+            meter_room_id: &info.meter_room_id,
+            building_id: &info.building_id,
+            campus_id: &info.campus_id,
+            room_number: &info.room_number,
+            recharge_amount: report.recharge_amount,
+            forecast: report.forecast.as_ref().map(|f| ForecastContext {
+                kwh_per_day: f.kwh_per_day,
+                cny_per_day: f.cny_per_day,
+                days_until_empty: f.days_until_empty,
+                depletion_time: f
+                    .depletion_time
+                    .map(|t| t.format("%Y-%m-%d %H:%M").to_string()),
+                window_days: f.window_days,
+            }),
+            level: report.alert.as_ref().map(|alert| alert.level.as_str()),
+            threshold: report.alert.as_ref().map(|alert| alert.threshold),
+        }
+    }
+}
+
+impl MessageTemplates {
+    pub fn new(config: &NotifyConfig, channel: &NotifyType) -> Self {
+        let catalog = Catalog::for_locale(config.locale);
+        let mut env = base_environment(catalog);
+        let (body, error_body) = match channel {
+            NotifyType::Console => (catalog.console, catalog.console_error),
+            NotifyType::Telegram => (catalog.telegram, catalog.telegram_error),
+            NotifyType::Email => (catalog.email, catalog.email_error),
+            NotifyType::Discord | NotifyType::Slack => (catalog.embed, catalog.embed_error),
+            _ => (catalog.generic, catalog.generic_error),
+        };
+        for (name, source) in [
+            ("builtin/title", catalog.title),
+            ("builtin/body", body),
+            ("builtin/error_body", error_body),
+        ] {
+            env.add_template(name, source)
+                .expect("built-in templates are valid");
+        }
+
+        for event in NotificationEvent::ALL {
+            for part in PARTS {
+                let Some(source) = user_template(config, channel, event, part) else {
+                    continue;
+                };
+                let name = format!("{}/{}", event.as_str(), part);
+                if let Err(e) = env.add_template_owned(name.clone(), source.to_string()) {
+                    warn!(
+                        "Invalid {} template {}, using the built-in one: {}",
+                        channel.as_str(),
+                        name,
+                        e
+                    );
+                }
+            }
+        }
+
+        Self { env }
+    }
+
+    /// Renders a power event (low balance, heartbeat, forecast, recharge).
+    pub fn render(&self, report: &PowerReport, event: NotificationEvent) -> RenderedMessage {
+        self.render_message(event, Some(PowerContext::new(report)), None)
+    }
+
+    /// Renders an error event (login failure, consecutive fetch failures).
+    pub fn render_error(&self, error_msg: &str, event: NotificationEvent) -> RenderedMessage {
+        self.render_message(event, None, Some(error_msg))
+    }
+
+    fn render_message(
+        &self,
+        event: NotificationEvent,
+        power: Option<PowerContext<'_>>,
+        error: Option<&str>,
+    ) -> RenderedMessage {
+        let now = Local::now();
+        let context = Value::from_serialize(MessageContext {
+            event: event.as_str(),
+            time: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+            timestamp: now.to_rfc3339(),
+            power,
+            error,
+        });
+        let builtin_body = if event.is_error() {
+            "builtin/error_body"
+        } else {
+            "builtin/body"
+        };
+
+        RenderedMessage {
+            title: self.render_part(event, "title", "builtin/title", &context),
+            body: self.render_part(event, "body", builtin_body, &context),
+        }
+    }
+
+    fn render_part(
+        &self,
+        event: NotificationEvent,
+        part: &str,
+        builtin: &str,
+        context: &Value,
+    ) -> String {
+        let name = format!("{}/{}", event.as_str(), part);
+        if let Ok(template) = self.env.get_template(&name) {
+            match template.render(context) {
+                Ok(text) => return text,
+                Err(e) => warn!(
+                    "Failed to render template {}, using the built-in one: {}",
+                    name, e
+                ),
+            }
+        }
+
+        self.env
+            .get_template(builtin)
+            .and_then(|template| template.render(context))
+            .unwrap_or_else(|e| {
+                error!("Failed to render built-in template {}: {}", builtin, e);
+                String::new()
+            })
+    }
+}
+
+/// What an error notification reports, turned into its `error` text by
+/// `error_message`.
+#[derive(Debug, Default, Serialize)]
+pub struct ErrorDetails<'a> {
+    pub account: Option<&'a str>,
+    pub detail: Option<&'a str>,
+    /// Consecutive failures, for `consecutive_fetch_failures`
+    pub count: Option<u32>,
+    /// A test notification from `test-notify`
+    pub test: bool,
+}
+
+/// Renders the text of an error event in the language of `locale`.
+pub fn error_message(
+    locale: Locale,
+    event: NotificationEvent,
+    details: &ErrorDetails<'_>,
+) -> String {
+    let catalog = Catalog::for_locale(locale);
+    let mut env = base_environment(catalog);
+    env.add_template("builtin/error_message", catalog.error_message)
+        .expect("built-in templates are valid");
+    let context = context! { event => event.as_str(), ..Value::from_serialize(details) };
+    env.get_template("builtin/error_message")
+        .and_then(|template| template.render(context))
+        .unwrap_or_else(|e| {
+            error!(
+                "Failed to render built-in template builtin/error_message: {}",
+                e
+            );
+            details.detail.unwrap_or_default().to_string()
+        })
+}
+
+/// Renders the request body of the webhook channel from
+/// `notify.webhook_body_template`, with the webhook payload as context.
+pub struct WebhookTemplate {
+    env: Environment<'static>,
+}
+
+impl WebhookTemplate {
+    pub fn new(source: &str) -> Result<Self, minijinja::Error> {
+        let mut env = webhook_environment();
+        env.add_template_owned("webhook/body", source.to_string())?;
+        Ok(Self { env })
+    }
+
+    pub fn render<S: Serialize>(&self, payload: &S) -> Result<String, minijinja::Error> {
+        self.env
+            .get_template("webhook/body")?
+            .render(Value::from_serialize(payload))
+    }
+}
+
+/// Checks the user templates of a notify config, returning one message per
+/// problem.
+pub fn validate(config: &NotifyConfig) -> Vec<String> {
+    let env = base_environment(Catalog::for_locale(config.locale));
+    let mut problems = Vec::new();
+
+    let mut check = |prefix: String, templates: &HashMap<String, MessageTemplate>| {
+        for (event, template) in templates {
+            if let Err(e) = event.parse::<NotificationEvent>() {
+                problems.push(format!("{}.{}: {}", prefix, event, e));
+                continue;
+            }
+            for (part, source) in [("title", &template.title), ("body", &template.body)] {
+                let Some(source) = source else {
+                    continue;
+                };
+                if let Err(e) = env.template_from_str(source) {
+                    problems.push(format!("{}.{}.{}: {}", prefix, event, part, e));
+                }
+            }
+        }
+    };
+
+    check("notify.templates".to_string(), &config.templates);
+    for (channel, templates) in &config.channel_templates {
+        check(format!("notify.channel_templates.{}", channel), templates);
+    }
+
+    if let Some(Err(e)) = (!config.webhook_body_template.is_empty())
+        .then(|| WebhookTemplate::new(&config.webhook_body_template))
+    {
+        problems.push(format!("notify.webhook_body_template: {}", e));
+    }
+
+    for channel in config.channel_templates.keys() {
+        if !NotifyType::ALL.iter().any(|t| t.as_str() == channel) {
+            problems.push(format!(
+                "notify.channel_templates.{}: unknown notify type",
+                channel
+            ));
+        }
+    }
+    problems
+}
+
+fn base_environment(catalog: &'static Catalog) -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.add_filter("fixed", fixed);
+    env.add_template("macros", catalog.macros)
+        .expect("built-in templates are valid");
+    env
+}
+
+fn webhook_environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.add_filter("fixed", fixed);
+    env
+}
+
+/// `{{ value|fixed }}` / `{{ value|fixed(1) }}`: formats a number with a fixed
+/// number of decimals (2 by default).
+fn fixed(value: f64, digits: Option<usize>) -> String {
+    format!("{:.*}", digits.unwrap_or(2), value)
+}
+
+fn user_template<'a>(
+    config: &'a NotifyConfig,
+    channel: &NotifyType,
+    event: NotificationEvent,
+    part: &str,
+) -> Option<&'a str> {
+    let select = |template: &'a MessageTemplate| match part {
+        "title" => template.title.as_deref(),
+        _ => template.body.as_deref(),
+    };
+    config
+        .channel_templates
+        .get(channel.as_str())
+        .and_then(|templates| templates.get(event.as_str()))
+        .and_then(select)
+        .or_else(|| config.templates.get(event.as_str()).and_then(select))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::PowerInfo;
+    use crate::config::AlertLevel;
+    use crate::forecast::ConsumptionForecast;
+    use crate::notify::LowBalanceAlert;
+
+    fn report() -> PowerReport {
+        let info: PowerInfo = serde_json::from_value(serde_json::json!({
+            "retcode": 0,
+            "msg": "",
+            "sydl": "26.9",
+            "syje": "4.4",
+            "dffjbh": "m1",
+            "roomName": "Room 101",
+            "roomId": "r1",
+            "buiId": "b1",
+            "areaid": "a1",
+            "fjh": "101",
+        }))
+        .unwrap();
+        PowerReport {
+            info,
+            forecast: Some(ConsumptionForecast {
+                kwh_per_day: 3.2,
+                cny_per_day: 2.1,
+                days_until_empty: Some(2.1),
+                depletion_time: Some(Local::now()),
+                window_days: 7.0,
+            }),
+            recharge_amount: Some(50.0),
+            alert: Some(LowBalanceAlert {
+                level: AlertLevel::Warning,
+                threshold: 5.0,
+                pushover_priority: 0,
+                ntfy_priority: 3,
+            }),
+        }
+    }
+
+    fn message(title: &str, body: &str) -> MessageTemplate {
+        MessageTemplate {
+            title: Some(title.to_string()),
+            body: Some(body.to_string()),
+        }
+    }
+
+    #[test]
+    fn builtin_templates_render_every_event_in_both_locales() {
+        let report = report();
+        for (locale, low_balance_title) in [
+            (Locale::En, "Low Balance Warning"),
+            (Locale::ZhCn, "余额不足"),
+        ] {
+            let config = NotifyConfig {
+                locale,
+                ..NotifyConfig::default()
+            };
+            for channel in NotifyType::ALL {
+                let templates = MessageTemplates::new(&config, &channel);
+                for event in NotificationEvent::ALL {
+                    let message = if event.is_error() {
+                        templates.render_error("boom", event)
+                    } else {
+                        templates.render(&report, event)
+                    };
+                    let case = format!("{:?} {} {}", locale, channel.as_str(), event.as_str());
+                    assert!(message.title.contains("UESTC Power Monitor"), "{}", case);
+                    if event.is_error() {
+                        assert!(message.body.contains("boom"), "{}", case);
+                    } else if matches!(channel, NotifyType::Discord | NotifyType::Slack) {
+                        // The balance is an embed field, the body only has the rest
+                        assert!(message.body.contains("3.20"), "{}", case);
+                    } else {
+                        assert!(message.body.contains("4.40"), "{}", case);
+                    }
+                }
+                let title = templates
+                    .render(&report, NotificationEvent::LowBalance)
+                    .title;
+                assert!(title.contains(low_balance_title), "{:?}", locale);
+            }
+        }
+    }
+
+    #[test]
+    fn error_messages_render_in_both_locales() {
+        let login = ErrorDetails {
+            account: Some("alice"),
+            detail: Some("bad password"),
+            ..ErrorDetails::default()
+        };
+        let fetch = ErrorDetails {
+            count: Some(3),
+            ..ErrorDetails::default()
+        };
+        let test = ErrorDetails {
+            test: true,
+            ..ErrorDetails::default()
+        };
+        for locale in [Locale::En, Locale::ZhCn] {
+            let text = error_message(locale, NotificationEvent::LoginFailure, &login);
+            assert!(text.contains("alice") && text.contains("bad password"));
+            let text = error_message(locale, NotificationEvent::ConsecutiveFetchFailures, &fetch);
+            assert!(text.contains('3'));
+            let text = error_message(locale, NotificationEvent::LoginFailure, &test);
+            assert!(!text.is_empty() && !text.contains("alice"));
+        }
+    }
+
+    #[test]
+    fn channel_templates_take_priority_over_event_templates() {
+        let mut config = NotifyConfig::default();
+        config.templates.insert(
+            "low_balance".to_string(),
+            message("global {{ room_id }}", "global body"),
+        );
+        config.channel_templates.insert(
+            "telegram".to_string(),
+            HashMap::from([(
+                "low_balance".to_string(),
+                MessageTemplate {
+                    title: Some("telegram {{ remaining_money|fixed(1) }}".to_string()),
+                    body: None,
+                },
+            )]),
+        );
+        let report = report();
+
+        let telegram = MessageTemplates::new(&config, &NotifyType::Telegram)
+            .render(&report, NotificationEvent::LowBalance);
+        assert_eq!(telegram.title, "telegram 4.4");
+        assert_eq!(telegram.body, "global body");
+
+        let console = MessageTemplates::new(&config, &NotifyType::Console)
+            .render(&report, NotificationEvent::LowBalance);
+        assert_eq!(console.title, "global r1");
+        assert_eq!(console.body, "global body");
+
+        let builtin = MessageTemplates::new(&NotifyConfig::default(), &NotifyType::Console)
+            .render(&report, NotificationEvent::Heartbeat);
+        let heartbeat = MessageTemplates::new(&config, &NotifyType::Console)
+            .render(&report, NotificationEvent::Heartbeat);
+        assert_eq!(heartbeat.title, builtin.title);
+    }
+
+    #[test]
+    fn invalid_templates_fall_back_to_the_builtin_ones() {
+        let mut config = NotifyConfig::default();
+        config.templates.insert(
+            "low_balance".to_string(),
+            message("{{ broken", "{{ remaining_money|unknown_filter }}"),
+        );
+        config.channel_templates.insert(
+            "console".to_string(),
+            HashMap::from([("heartbeat".to_string(), message("{% if %}", "ok"))]),
+        );
+        // Unknown filters only fail when rendering
+        assert_eq!(validate(&config).len(), 2);
+        let report = report();
+        let builtin = MessageTemplates::new(&NotifyConfig::default(), &NotifyType::Console);
+        let templates = MessageTemplates::new(&config, &NotifyType::Console);
+
+        let message = templates.render(&report, NotificationEvent::LowBalance);
+        let expected = builtin.render(&report, NotificationEvent::LowBalance);
+        assert_eq!(message.title, expected.title);
+        assert_eq!(message.body, expected.body);
+
+        let message = templates.render(&report, NotificationEvent::Heartbeat);
+        let expected = builtin.render(&report, NotificationEvent::Heartbeat);
+        assert_eq!(message.title, expected.title);
+        assert_eq!(message.body, "ok");
+    }
+}
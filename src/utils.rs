@@ -1,3 +1,4 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use std::future::Future;
 use std::time::Duration;
 use tokio::time::sleep;
@@ -19,27 +20,63 @@ where
     let mut delay = initial_delay;
     for i in 0..max_retries {
         debug!("Retry attempt {}/{}", i + 1, max_retries);
-        match operation().await {
+        let error = match operation().await {
             Ok(value) => {
                 debug!("Operation succeeded on attempt {}/{}", i + 1, max_retries);
                 return Ok(value);
             }
-            Err(e) => {
-                if i == max_retries - 1 {
-                    debug!("All retry attempts exhausted");
-                    return Err(e);
-                }
-                warn!(
-                    "Operation failed (attempt {}/{}): request error (details redacted). Retrying in {:?}...",
-                    i + 1,
-                    max_retries,
-                    delay
-                );
-                sleep(delay).await;
-                delay *= 2; // Exponential backoff
-                debug!("Next retry delay: {:?}", delay);
-            }
+            Err(e) => e,
+        };
+        if i == max_retries - 1 {
+            debug!("All retry attempts exhausted");
+            return Err(error);
         }
+        // Not held across the sleep, errors like `Box<dyn Error>` are not `Send`
+        drop(error);
+        warn!(
+            "Operation failed (attempt {}/{}): request error (details redacted). Retrying in {:?}...",
+            i + 1,
+            max_retries,
+            delay
+        );
+        sleep(delay).await;
+        delay *= 2; // Exponential backoff
+        debug!("Next retry delay: {:?}", delay);
     }
     unreachable!()
 }
+
+/// Parses a user supplied time: RFC 3339, or `YYYY-MM-DD[ HH:MM:SS]` in the
+/// local timezone.
+pub fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|local| local.with_timezone(&Utc))
+}
+
+/// Like `parse_time`, but a date without a time stands for the end of that
+/// day, so it can be used as an inclusive upper bound.
+pub fn parse_end_time(value: &str) -> Option<DateTime<Utc>> {
+    let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") else {
+        return parse_time(value);
+    };
+    let next_day = date.succ_opt()?.and_hms_opt(0, 0, 0)?;
+    Local
+        .from_local_datetime(&next_day)
+        .earliest()
+        .map(|local| local.with_timezone(&Utc) - chrono::Duration::microseconds(1))
+}